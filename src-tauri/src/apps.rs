@@ -1,10 +1,17 @@
 use crate::app_note::{append_note, create_note};
-use std::error::Error;
+use crate::error::RuntimeError;
+
+fn failed(func: &str, reason: impl ToString) -> RuntimeError {
+    RuntimeError::AppCallFailed {
+        function: func.to_string(),
+        reason: reason.to_string(),
+    }
+}
 
 /*
 ** app functions
 */
-pub fn call(func: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
+pub fn call(func: &str, args: &[&str]) -> Result<(), RuntimeError> {
     match func {
         /*
          ** this function basically use osascript to append text to the last most recent note.
@@ -12,12 +19,12 @@ pub fn call(func: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
          */
         "append_note" => {
             if args.len() != 1 {
-                return Err(
-                    format!("append_note expects 1 argument, but got {}", args.len()).into(),
-                );
+                return Err(failed(
+                    func,
+                    format!("expects 1 argument, but got {}", args.len()),
+                ));
             }
-            append_note(&args[0])?;
-            Ok(())
+            append_note(args[0]).map_err(|e| failed(func, e))
         }
 
         /*
@@ -26,19 +33,17 @@ pub fn call(func: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
          */
         "create_note" => {
             if args.len() != 2 {
-                return Err(
-                    format!("create_note expects 2 arguments, but got {}", args.len()).into(),
-                );
+                return Err(failed(
+                    func,
+                    format!("expects 2 arguments, but got {}", args.len()),
+                ));
             }
-            create_note(&args[0], &args[1])?;
-            Ok(())
+            create_note(args[0], args[1]).map_err(|e| failed(func, e))
         }
 
         /*************************************************************************************
          ** if the function is not found, return an error in case of unexpected function call.
          */
-        _ => {
-            return Err(format!("Unknown function: {func}").into());
-        }
+        _ => Err(failed(func, "unknown function")),
     }
 }
@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_opener::OpenerExt;
+use tracing::info;
+
+use crate::error::RuntimeError;
+use crate::events::app_handle;
+use crate::paths::app_data_subdir;
+use crate::utils::now_ms;
+
+const ARTIFACTS_DIR: &str = "artifacts";
+const MANIFEST_FILE: &str = "artifacts.json";
+const MAX_SESSION_ID_LEN: usize = 64;
+const MAX_FILE_STEM_LEN: usize = 60;
+
+/*
+** manifests are read, changed and written back whole
+*/
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    Pdf,
+}
+
+impl ArtifactKind {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArtifactKind::Pdf => "pdf",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            ArtifactKind::Pdf => "application/pdf",
+        }
+    }
+}
+
+/*
+** where an artifact came from, as far as we know
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactSource {
+    pub title: Option<String>,
+    pub url: Option<String>,
+}
+
+/*
+** a file an agent session produced. `id` is the file name inside the
+** session folder, so it stays stable across restarts.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artifact {
+    pub id: String,
+    pub session_id: String,
+    pub kind: ArtifactKind,
+    pub path: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    pub title: Option<String>,
+    pub source_url: Option<String>,
+    pub created_at_ms: u64,
+}
+
+/*
+** session ids come from the frontend and name a folder, so they get the
+** same treatment as profile names
+*/
+pub fn validate_session_id(session_id: &str) -> Result<(), RuntimeError> {
+    let valid = !session_id.is_empty()
+        && session_id.len() <= MAX_SESSION_ID_LEN
+        && !session_id.starts_with('.')
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(RuntimeError::InvalidInput {
+            reason: format!(
+                "Invalid session id '{session_id}': use 1 to {MAX_SESSION_ID_LEN} letters, digits, '-', '_' or '.'"
+            ),
+        })
+    }
+}
+
+/*
+** <app data>/artifacts/<session id>/{artifacts.json, files...}
+*/
+fn session_dir(session_id: &str) -> Result<PathBuf, RuntimeError> {
+    validate_session_id(session_id)?;
+    let dir = app_data_subdir(ARTIFACTS_DIR)?.join(session_id);
+    fs::create_dir_all(&dir).map_err(|e| RuntimeError::storage(&dir, e))?;
+    Ok(dir)
+}
+
+fn read_manifest(dir: &Path) -> Result<Vec<Artifact>, RuntimeError> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = fs::read_to_string(&path).map_err(|e| RuntimeError::storage(&path, e))?;
+    serde_json::from_str(&raw).map_err(|e| RuntimeError::storage(&path, e))
+}
+
+fn write_manifest(dir: &Path, artifacts: &[Artifact]) -> Result<(), RuntimeError> {
+    let path = dir.join(MANIFEST_FILE);
+    let raw = serde_json::to_string_pretty(artifacts).map_err(RuntimeError::internal)?;
+    fs::write(&path, raw).map_err(|e| RuntimeError::storage(&path, e))
+}
+
+/*
+** "Order #123 – Receipt" -> "order-123-receipt"
+*/
+fn file_stem(title: &str) -> String {
+    let mut stem = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            stem.push(c.to_ascii_lowercase());
+        } else if !stem.is_empty() && !stem.ends_with('-') {
+            stem.push('-');
+        }
+        if stem.len() >= MAX_FILE_STEM_LEN {
+            break;
+        }
+    }
+    let stem = stem.trim_end_matches('-');
+    if stem.is_empty() {
+        "page".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn unique_file_name(dir: &Path, stem: &str, extension: &str) -> String {
+    let mut name = format!("{stem}.{extension}");
+    let mut n = 2;
+    while dir.join(&name).exists() || name == MANIFEST_FILE {
+        name = format!("{stem}-{n}.{extension}");
+        n += 1;
+    }
+    name
+}
+
+/*
+** write `bytes` into the session folder, named after the source title, and
+** add it to the session's manifest
+*/
+pub fn store(
+    session_id: &str,
+    kind: ArtifactKind,
+    bytes: &[u8],
+    source: ArtifactSource,
+) -> Result<Artifact, RuntimeError> {
+    let dir = session_dir(session_id)?;
+    let _guard = MANIFEST_LOCK.lock().unwrap();
+    let mut manifest = read_manifest(&dir)?;
+
+    let stem = file_stem(source.title.as_deref().unwrap_or_default());
+    let name = unique_file_name(&dir, &stem, kind.extension());
+    let path = dir.join(&name);
+    fs::write(&path, bytes).map_err(|e| RuntimeError::storage(&path, e))?;
+
+    let artifact = Artifact {
+        id: name,
+        session_id: session_id.to_string(),
+        kind,
+        path: path.to_string_lossy().to_string(),
+        mime_type: kind.mime_type().to_string(),
+        size_bytes: bytes.len() as u64,
+        title: source.title,
+        source_url: source.url,
+        created_at_ms: now_ms(),
+    };
+    manifest.push(artifact.clone());
+    write_manifest(&dir, &manifest)?;
+    info!(session_id, artifact_id = %artifact.id, size_bytes = artifact.size_bytes, "stored artifact");
+    Ok(artifact)
+}
+
+/*
+** oldest first; files deleted behind our back are left out
+*/
+pub fn list_artifacts(session_id: &str) -> Result<Vec<Artifact>, RuntimeError> {
+    let dir = session_dir(session_id)?;
+    let _guard = MANIFEST_LOCK.lock().unwrap();
+    Ok(read_manifest(&dir)?
+        .into_iter()
+        .filter(|artifact| Path::new(&artifact.path).exists())
+        .collect())
+}
+
+fn find_artifact(session_id: &str, artifact_id: &str) -> Result<Artifact, RuntimeError> {
+    list_artifacts(session_id)?
+        .into_iter()
+        .find(|artifact| artifact.id == artifact_id)
+        .ok_or_else(|| RuntimeError::ArtifactNotFound {
+            session_id: session_id.to_string(),
+            artifact_id: artifact_id.to_string(),
+        })
+}
+
+/*
+** open with the system's default app, or show it in the file manager.
+** going through here instead of the opener plugin's JS API keeps the
+** frontend from needing a filesystem scope for the artifacts folder.
+*/
+pub fn open_artifact(
+    session_id: &str,
+    artifact_id: &str,
+    reveal: bool,
+) -> Result<(), RuntimeError> {
+    let artifact = find_artifact(session_id, artifact_id)?;
+    let opener = app_handle()?.opener();
+    let opened = if reveal {
+        opener.reveal_item_in_dir(&artifact.path)
+    } else {
+        opener.open_path(&artifact.path, None::<&str>)
+    };
+    opened.map_err(|e| RuntimeError::internal(format!("Failed to open {}: {e}", artifact.path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ids_cannot_leave_the_artifacts_folder() {
+        for id in ["agent-7", "run_2024.10.18", "a"] {
+            assert!(validate_session_id(id).is_ok(), "{id}");
+        }
+        let too_long = "x".repeat(MAX_SESSION_ID_LEN + 1);
+        for id in [
+            "",
+            ".",
+            "..",
+            "../etc",
+            "a/b",
+            r"a\b",
+            "/tmp",
+            ".hidden",
+            "sp ace",
+            too_long.as_str(),
+        ] {
+            assert!(
+                matches!(
+                    validate_session_id(id),
+                    Err(RuntimeError::InvalidInput { .. })
+                ),
+                "{id}"
+            );
+        }
+    }
+
+    #[test]
+    fn file_stems_are_lowercase_dashes_only() {
+        assert_eq!(file_stem("Order #123 – Receipt"), "order-123-receipt");
+        assert_eq!(file_stem("../../etc/passwd"), "etc-passwd");
+        assert_eq!(file_stem("  --Hello--  "), "hello");
+        assert_eq!(file_stem("日本語"), "page");
+        assert_eq!(file_stem(""), "page");
+
+        let stem = file_stem(&"word ".repeat(40));
+        assert!(stem.len() <= MAX_FILE_STEM_LEN);
+        assert!(!stem.ends_with('-'));
+    }
+}
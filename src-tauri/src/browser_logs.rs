@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::error::RuntimeError;
+use crate::paths::app_data_subdir;
+use crate::utils::now_ms;
+use tracing::warn;
+
+pub const LOG_TAIL_CAPACITY: usize = 1000;
+const LOG_FILE_MAX_BYTES: u64 = 1024 * 1024;
+const LOG_FILE_KEEP: usize = 3;
+
+/*
+** output of the most recent launch attempt, kept even when the launch
+** failed and no instance was ever registered
+*/
+pub static LAST_LAUNCH_LOGS: Lazy<Mutex<Option<BrowserLogs>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for LogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogStream::Stdout => write!(f, "stdout"),
+            LogStream::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrowserLogLine {
+    pub timestamp_ms: u64,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+/*
+** bounded ring buffer of a browser's output, shared with its reader threads
+*/
+#[derive(Clone)]
+pub struct BrowserLogs {
+    lines: Arc<Mutex<VecDeque<BrowserLogLine>>>,
+    capacity: usize,
+}
+
+impl fmt::Debug for BrowserLogs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserLogs")
+            .field("lines", &self.lines.lock().unwrap().len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl BrowserLogs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    fn push(&self, line: BrowserLogLine) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn tail(&self, count: usize) -> Vec<BrowserLogLine> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(count);
+        lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+/*
+** size-capped log file: `<name>.log` rolls over to `<name>.1.log` ... `<name>.N.log`
+*/
+#[derive(Debug)]
+pub struct RotatingLogFile {
+    dir: PathBuf,
+    name: String,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLogFile {
+    pub fn open_in_app_data(name: &str) -> Result<Self, RuntimeError> {
+        let dir = app_data_subdir("browser-logs")?;
+        let path = dir.join(format!("{name}.log"));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| RuntimeError::storage(&path, e))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            dir,
+            name: name.to_string(),
+            file,
+            written,
+        })
+    }
+
+    fn path(&self, generation: usize) -> PathBuf {
+        match generation {
+            0 => self.dir.join(format!("{}.log", self.name)),
+            n => self.dir.join(format!("{}.{n}.log", self.name)),
+        }
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for generation in (1..LOG_FILE_KEEP).rev() {
+            let from = self.path(generation - 1);
+            if from.exists() {
+                fs::rename(&from, self.path(generation))?;
+            }
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, pid: u32, line: &BrowserLogLine) {
+        if self.written >= LOG_FILE_MAX_BYTES {
+            if let Err(e) = self.rotate() {
+                warn!("failed to rotate browser log {}: {e}", self.name);
+            }
+        }
+        let text = format!(
+            "{} [{pid}] [{}] {}\n",
+            line.timestamp_ms, line.stream, line.line
+        );
+        if self.file.write_all(text.as_bytes()).is_ok() {
+            self.written += text.len() as u64;
+        }
+    }
+}
+
+pub type SharedLogFile = Arc<Mutex<RotatingLogFile>>;
+
+/*
+** read one output stream line by line on its own thread until the process
+** closes it. `on_line` lets the launcher watch for things like the DevTools line.
+*/
+pub fn capture_stream<R, F>(
+    reader: R,
+    stream: LogStream,
+    pid: u32,
+    logs: BrowserLogs,
+    log_file: Option<SharedLogFile>,
+    mut on_line: F,
+) where
+    R: Read + Send + 'static,
+    F: FnMut(&str) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else { break };
+            on_line(&line);
+
+            let entry = BrowserLogLine {
+                timestamp_ms: now_ms(),
+                stream,
+                line,
+            };
+            if let Some(file) = &log_file {
+                file.lock().unwrap().write_line(pid, &entry);
+            }
+            logs.push(entry);
+        }
+    });
+}
@@ -1,17 +1,35 @@
-use std::process::{Command, Stdio};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
 use std::time::Duration;
 
-use once_cell::sync::Lazy;
-use reqwest::Client;
-use tokio::sync::Mutex;
+use futures_util::future::join_all;
+use tokio::sync::oneshot;
 
-use crate::network::{
-    extract_port_from_ws_url, get_browser_websocket_url, scan_for_existing_browser_instances,
+use crate::browser_logs::{
+    capture_stream, BrowserLogs, LogStream, RotatingLogFile, LAST_LAUNCH_LOGS, LOG_TAIL_CAPACITY,
 };
-use crate::platform::detect_browsers;
-use crate::sketchs::ManageableBrowserInstance;
-
-pub static MANAGED_BROWSER: Lazy<Mutex<Option<ManageableBrowserInstance>>> = Lazy::new(|| Mutex::new(None));
+use crate::cdp::CdpSession;
+use crate::custom_browsers::available_browsers;
+use crate::downloads::manage_launched;
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+use crate::events::{emit_lifecycle, BrowserLifecycleEvent, BROWSER_LAUNCHED};
+use crate::http::{self, HttpProfile};
+use crate::launch_options::LaunchOptions;
+use crate::network::get_browser_info;
+#[cfg(unix)]
+use crate::pipe::spawn_with_debug_pipe;
+use crate::profiles::prepare_profile;
+use crate::readiness::{
+    clear_active_port_file, listening_watcher, wait_until_ready, ActivePort, LAUNCH_TIMEOUT,
+};
+use crate::registry::{InstanceKey, TakeIf, TakenInstance, BROWSER_MANAGER};
+use crate::scanner::scan_for_existing_browser_instances;
+use crate::sketchs::{DebugTransport, LaunchedInstance, ManageableBrowserInstance};
+use crate::utils::{browser_id_for_path, is_arc_path, now_ms};
+use crate::watchdog::supervise;
+use tracing::{debug, error, info, warn};
 
 fn get_allowed_origins() -> String {
     let is_dev = cfg!(debug_assertions);
@@ -35,102 +53,138 @@ fn get_allowed_origins() -> String {
     }
 }
 
+/*
+** open the Rust-side DevTools session. a failure here is not fatal:
+** the webview can still drive the browser through the websocket url.
+*/
+pub async fn connect_cdp(ws_url: &str) -> Option<CdpSession> {
+    match CdpSession::connect(ws_url).await {
+        Ok(session) => Some(session),
+        Err(e) => {
+            warn!("could not open CDP session: {e}");
+            None
+        }
+    }
+}
+
+/*
+** CDP session of an instance (or the selected one), (re)connecting lazily if needed
+*/
+pub async fn managed_cdp_session(instance_id: Option<&str>) -> Result<CdpSession, RuntimeError> {
+    BROWSER_MANAGER.cdp_session(instance_id).await
+}
+
+/*
+** remember a browser we did not launch ourselves (or return the id we already gave it).
+** `path` is the executable for local browsers and the endpoint URL for remote ones.
+*/
+pub async fn adopt_external_instance(
+    path: &str,
+    browser_id: &str,
+    endpoint: CdpEndpoint,
+    ws_url: &str,
+) -> String {
+    if let Some(instance_id) = BROWSER_MANAGER
+        .find(InstanceKey::WsUrl(ws_url.to_string()))
+        .await
+    {
+        let _ = BROWSER_MANAGER.select(&instance_id).await;
+        return instance_id;
+    }
+
+    /*
+     ** someone else may adopt the same browser while we connect; the manager
+     ** keeps whichever registers first
+     */
+    let cdp = connect_cdp(ws_url).await;
+    let (instance_id, inserted) = BROWSER_MANAGER
+        .adopt(ManageableBrowserInstance {
+            child: None,
+            browser_id: browser_id.to_string(),
+            path: path.to_string(),
+            transport: DebugTransport::Tcp,
+            endpoint: Some(endpoint),
+            ws_url: Some(ws_url.to_string()),
+            launched_by_app: false,
+            cdp,
+            logs: None,
+            launch: None,
+        })
+        .await;
+    if inserted {
+        supervise(instance_id.clone());
+    }
+    instance_id
+}
+
+/*
+** forget an instance and make sure its process is gone
+*/
+async fn discard_instance(instance_id: &str) {
+    if let Some(mut child) = BROWSER_MANAGER
+        .remove(instance_id)
+        .await
+        .and_then(|instance| instance.child)
+    {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
 #[allow(dead_code)]
 pub async fn get_running_instance(target_browser_path: &str) -> Option<String> {
-    let mut managed_browser_guard = MANAGED_BROWSER.lock().await;
+    let known = match BROWSER_MANAGER
+        .find(InstanceKey::Path(target_browser_path.to_string()))
+        .await
+    {
+        Some(instance_id) => BROWSER_MANAGER.snapshot(Some(&instance_id)).await.ok(),
+        None => None,
+    };
 
-    if let Some(instance) = &mut *managed_browser_guard {
-        if instance.path == target_browser_path {
-            /*
-             ** is the child process still running?
-             */
-            if let Some(ref mut child) = instance.child {
-                match child.try_wait() {
-                    Ok(Some(_)) => {
-                        println!("browser process has exited");
-                        *managed_browser_guard = None;
-                    }
-                    Ok(None) => {
-                        println!("reusing the runtime instance at {}", instance.port);
-                        let client = Client::builder()
-                            .timeout(Duration::from_secs(2))
-                            .build()
-                            .ok()?;
-                        let version_url =
-                            format!("http://127.0.0.1:{}/json/version", instance.port);
-                        match client.get(&version_url).send().await {
-                            Ok(resp) if resp.status().is_success() => {
-                                println!("Running at -> {}", instance.ws_url);
-                                return Some(instance.ws_url.clone());
-                            }
-                            _ => {
-                                println!("failed to reconnect. killing it.");
-                                let _ = child.kill();
-                                let _ = child.wait();
-                                *managed_browser_guard = None;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "failed to check if browser is running: {e}. will launch a new one."
-                        );
-                        let _ = child.kill();
-                        *managed_browser_guard = None;
-                    }
-                }
-            } else {
-                /*
-                 ** ping endpoint directly
-                 */
-                println!("instance had no child handle; checking endpoint availability...");
-                let client = Client::builder()
-                    .timeout(Duration::from_secs(2))
-                    .build()
-                    .ok()?;
-                let version_url = format!("http://127.0.0.1:{}/json/version", instance.port);
-                match client.get(&version_url).send().await {
+    if let Some(instance) = known {
+        let instance_id = instance.instance_id;
+        match (instance.exited, instance.endpoint, instance.ws_url) {
+            (Some(reason), _, _) => {
+                info!("browser process {instance_id} has exited: {reason}");
+                discard_instance(&instance_id).await;
+            }
+            (None, Some(endpoint), Some(ws_url)) => {
+                info!("reusing the runtime instance at {endpoint}");
+                match http::get(HttpProfile::DevTools, &endpoint.http_url("/json/version")).await {
                     Ok(resp) if resp.status().is_success() => {
-                        return Some(instance.ws_url.clone());
+                        debug!("Running at -> {ws_url}");
+                        let _ = BROWSER_MANAGER.select(&instance_id).await;
+                        return Some(ws_url);
                     }
                     _ => {
-                        *managed_browser_guard = None;
+                        warn!("failed to reconnect. killing it.");
+                        discard_instance(&instance_id).await;
                     }
                 }
             }
-        } else {
             /*
-             ** shut the old one down first
+             ** pipe browsers have no endpoint to hand out
              */
-            println!(
-                "Switching browser. Closing previous instance: {}",
-                instance.path
-            );
-            if let Some(mut child) = instance.child.take() {
-                let _ = child.kill();
-                let _ = child.wait();
-            }
-            *managed_browser_guard = None;
+            _ => {}
         }
     }
 
     /*
      ** reconnect to the existing instance
      */
-    let browsers = detect_browsers();
+    let browsers = available_browsers().await;
     if let Some(target_browser) = browsers.iter().find(|b| b.path == target_browser_path) {
-        if let Some(ws_url) = scan_for_existing_browser_instances(&target_browser.id).await {
-            let port = extract_port_from_ws_url(&ws_url).ok()?.parse().ok()?;
-            *managed_browser_guard = Some(ManageableBrowserInstance {
-                child: None,
-                path: target_browser_path.to_string(),
-                port,
-                ws_url: ws_url.clone(),
-                launched_by_app: false,
-            });
-
-            println!(
-                "reconnected to existing {} instance at {}",
+        if let Some(ws_url) = scan_for_existing_browser_instances(target_browser.family()).await {
+            let endpoint = CdpEndpoint::parse(&ws_url).ok()?;
+            let instance_id = adopt_external_instance(
+                target_browser_path,
+                target_browser.family(),
+                endpoint,
+                &ws_url,
+            )
+            .await;
+            info!(
+                "reconnected to existing {} instance {instance_id} at {}",
                 target_browser.id, ws_url
             );
             return Some(ws_url);
@@ -141,163 +195,533 @@ pub async fn get_running_instance(target_browser_path: &str) -> Option<String> {
 }
 
 /*
-** launch a fresh instance
+** where a fresh instance exposes its DevTools protocol
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum LaunchTransport {
+    Pipe,
+    Port(u16),
+}
+
+/*
+** everything needed to start (or restart) a browser the same way again.
+** without a profile name the browser gets its default one ("chrome", "edge", ...).
 */
-pub async fn launch_new_instance(target_browser_path: &str, port: u16) -> Result<String, String> {
+#[derive(Debug, Clone)]
+pub struct LaunchSpec {
+    pub path: String,
+    pub transport: LaunchTransport,
+    pub log_to_file: bool,
+    pub profile: Option<String>,
+    pub options: LaunchOptions,
+}
+
+/*
+** launch a fresh instance, register it and put it under the watchdog
+*/
+pub async fn launch_new_instance(spec: LaunchSpec) -> Result<LaunchedInstance, RuntimeError> {
+    let instance = spawn_instance(spec).await?;
+    let browser_id = instance.browser_id.clone();
+    let ws_url = instance.ws_url.clone();
+
+    let instance_id = BROWSER_MANAGER.insert(instance).await;
+    info!("browser {instance_id} launched successfully");
+
+    emit_lifecycle(
+        BROWSER_LAUNCHED,
+        BrowserLifecycleEvent {
+            instance_id: instance_id.clone(),
+            browser_id,
+            ws_url: ws_url.clone(),
+            reason: None,
+            restart_attempt: None,
+        },
+    );
+    supervise(instance_id.clone());
+    manage_launched(instance_id.clone());
+
+    Ok(LaunchedInstance {
+        instance_id,
+        ws_url,
+        starter_page_error: None,
+    })
+}
+
+/*
+** start the browser process and wait until it speaks CDP. the caller decides
+** where the resulting instance goes.
+*/
+pub async fn spawn_instance(
+    mut spec: LaunchSpec,
+) -> Result<ManageableBrowserInstance, RuntimeError> {
     let is_dev = cfg!(debug_assertions);
+    let target_browser_path = spec.path.as_str();
 
-    println!("launching browser: {target_browser_path} with --remote-debugging-port={port}");
-    println!(
+    match spec.transport {
+        LaunchTransport::Pipe => {
+            info!("launching browser: {target_browser_path} with --remote-debugging-pipe")
+        }
+        LaunchTransport::Port(port) => {
+            info!("launching browser: {target_browser_path} with --remote-debugging-port={port}");
+            debug!("allowed origins: {}", get_allowed_origins());
+        }
+    }
+    debug!(
         "environment: {} mode",
         if is_dev { "development" } else { "production" }
     );
-    println!("allowed origins: {}", get_allowed_origins());
 
-    let lower = target_browser_path.to_lowercase();
-    let is_edge = lower.contains("edge") || lower.contains("msedge");
-    let is_chrome = lower.contains("chrome");
-    let is_arc = lower.contains("arc");
+    spec.options.validate()?;
+    if spec.options.headless() {
+        info!("running headless ({:?} preset)", spec.options.preset);
+    }
+
+    let is_arc = is_arc_path(target_browser_path);
+    let browser_id = browser_id_for_path(target_browser_path);
+    let profile = spec
+        .profile
+        .get_or_insert_with(|| browser_id.to_string())
+        .clone();
+    let user_data_dir = prepare_profile(&profile, browser_id)?;
+    info!("using profile '{profile}' at {}", user_data_dir.display());
 
     let mut command = Command::new(target_browser_path);
+    match spec.transport {
+        LaunchTransport::Pipe => {
+            command.arg("--remote-debugging-pipe");
+        }
+        LaunchTransport::Port(port) => {
+            command
+                .arg(format!("--remote-debugging-port={port}"))
+                .arg("--remote-debugging-address=127.0.0.1")
+                .arg(format!("--remote-allow-origins={}", get_allowed_origins()));
+        }
+    }
     command
-        .arg(format!("--remote-debugging-port={port}"))
+        .arg(format!("--user-data-dir={}", user_data_dir.display()))
+        .args(spec.options.browser_args())
+        .envs(spec.options.browser_env());
+
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    match spec.transport {
+        LaunchTransport::Pipe => launch_over_pipe(command, spec, is_arc).await,
+        LaunchTransport::Port(port) => {
+            launch_over_port(command, spec, is_arc, port, &user_data_dir).await
+        }
+    }
+}
+
+const CDP_PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/*
+** start the binary headless on a scratch profile just long enough to see it
+** announce a DevTools endpoint; returns the product version it reports
+*/
+pub async fn probe_cdp_support(path: &str, extra_args: &[String]) -> Result<String, RuntimeError> {
+    let user_data_dir = std::env::temp_dir().join(format!("runtime-cdp-probe-{}", now_ms()));
+    std::fs::create_dir_all(&user_data_dir)
+        .map_err(|e| RuntimeError::storage(&user_data_dir, e))?;
+
+    let mut child = Command::new(path)
+        .arg("--headless=new")
+        .arg("--remote-debugging-port=0")
         .arg("--remote-debugging-address=127.0.0.1")
-        .arg(format!("--remote-allow-origins={}", get_allowed_origins()))
         .arg("--no-first-run")
         .arg("--no-default-browser-check")
-        .arg("--disable-background-timer-throttling")
-        .arg("--disable-backgrounding-occluded-windows")
-        .arg("--disable-renderer-backgrounding")
-        .arg("--enable-automation");
+        .arg(format!("--user-data-dir={}", user_data_dir.display()))
+        .args(extra_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| RuntimeError::LaunchFailed {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })?;
 
-    if is_chrome {
-        #[cfg(any(target_os = "macos", target_os = "linux"))]
-        command.arg("--user-data-dir=/tmp/chrome-debug-profile");
-    } else if is_edge {
-        #[cfg(any(target_os = "macos", target_os = "linux"))]
-        command.arg("--user-data-dir=/tmp/edge-debug-profile");
-    } else if is_arc {
-        #[cfg(any(target_os = "macos", target_os = "linux"))]
-        command.arg("--user-data-dir=/tmp/arc-debug-profile");
+    let (on_line, listening) = listening_watcher();
+    if let Some(stderr) = child.stderr.take() {
+        capture_stream(
+            stderr,
+            LogStream::Stderr,
+            child.id(),
+            BrowserLogs::new(LOG_TAIL_CAPACITY),
+            None,
+            on_line,
+        );
     }
 
-    /*
-     ** give the browser a moment to finish booting
-     */
-    let mut child_process = command
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("failed to launch browser: {e}"))?;
+    let ready = wait_until_ready(
+        &mut child,
+        Some(&user_data_dir),
+        listening,
+        CDP_PROBE_TIMEOUT,
+    )
+    .await;
+    let version = match ready {
+        Ok(active) => get_browser_info(&CdpEndpoint::local(active.port))
+            .await
+            .map(|version| version.version.unwrap_or(version.product))
+            .map_err(|e| RuntimeError::LaunchFailed {
+                path: path.to_string(),
+                reason: format!("opened a DevTools port but did not answer on it: {e}"),
+            }),
+        Err(e) => Err(RuntimeError::LaunchFailed {
+            path: path.to_string(),
+            reason: format!("does not look like a CDP browser: {e}"),
+        }),
+    };
 
-    tokio::time::sleep(Duration::from_secs(2)).await;
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = std::fs::remove_dir_all(&user_data_dir);
+    version
+}
 
-    match child_process.try_wait() {
-        Ok(Some(status)) => {
-            return Err(format!(
-                "Browser process exited immediately with status: {status}"
-            ))
+/*
+** drain stdout/stderr into the instance's ring buffer (and optionally a rotating
+** file) so a chatty browser never blocks on a full pipe. stderr is also watched
+** for the "DevTools listening on" line. the file is named after the profile,
+** which only one running instance can use at a time.
+*/
+fn capture_browser_output(
+    child: &mut Child,
+    spec: &LaunchSpec,
+) -> (BrowserLogs, oneshot::Receiver<ActivePort>) {
+    let logs = BrowserLogs::new(LOG_TAIL_CAPACITY);
+    *LAST_LAUNCH_LOGS.lock().unwrap() = Some(logs.clone());
+
+    let log_file = if spec.log_to_file {
+        let name = spec
+            .profile
+            .as_deref()
+            .unwrap_or_else(|| browser_id_for_path(&spec.path));
+        match RotatingLogFile::open_in_app_data(name) {
+            Ok(file) => Some(Arc::new(std::sync::Mutex::new(file))),
+            Err(e) => {
+                warn!("browser output will not be written to disk: {e}");
+                None
+            }
         }
-        Ok(None) => {}
-        Err(e) => return Err(format!("Failed to check browser process status: {e}")),
+    } else {
+        None
+    };
+
+    let pid = child.id();
+    let (on_stderr_line, listening) = listening_watcher();
+
+    if let Some(stdout) = child.stdout.take() {
+        capture_stream(
+            stdout,
+            LogStream::Stdout,
+            pid,
+            logs.clone(),
+            log_file.clone(),
+            |_| {},
+        );
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture_stream(
+            stderr,
+            LogStream::Stderr,
+            pid,
+            logs.clone(),
+            log_file,
+            on_stderr_line,
+        );
+    }
+
+    (logs, listening)
+}
+
+#[cfg(unix)]
+async fn launch_over_pipe(
+    mut command: Command,
+    spec: LaunchSpec,
+    is_arc: bool,
+) -> Result<ManageableBrowserInstance, RuntimeError> {
+    let browser_id = browser_id_for_path(&spec.path);
+    let (mut child_process, pipe) =
+        spawn_with_debug_pipe(&mut command).map_err(|e| RuntimeError::LaunchFailed {
+            path: spec.path.clone(),
+            reason: e.to_string(),
+        })?;
+    let (logs, _) = capture_browser_output(&mut child_process, &spec);
+
+    /*
+     ** commands sent before the browser is up just sit in the pipe,
+     ** so the first answer doubles as the readiness check
+     */
+    let cdp = CdpSession::connect_pipe(pipe);
+    if let Err(e) = cdp.send("Browser.getVersion", serde_json::json!({})).await {
+        let _ = child_process.kill();
+        let _ = child_process.wait();
+        return Err(RuntimeError::LaunchFailed {
+            path: spec.path.clone(),
+            reason: format!("browser did not answer on the debugging pipe: {e}"),
+        });
+    }
+
+    /*
+     ** Arc needs a window even when the starter page is turned off
+     */
+    if is_arc {
+        let url = spec
+            .options
+            .starter_page
+            .as_deref()
+            .unwrap_or("about:blank");
+        let _ = cdp
+            .send("Target.createTarget", serde_json::json!({ "url": url }))
+            .await;
     }
 
+    info!("browser {} is up on the debugging pipe", spec.path);
+    Ok(ManageableBrowserInstance {
+        browser_id: browser_id.to_string(),
+        path: spec.path.clone(),
+        transport: DebugTransport::Pipe,
+        endpoint: None,
+        ws_url: None,
+        child: Some(child_process),
+        launched_by_app: true,
+        cdp: Some(cdp),
+        logs: Some(logs),
+        launch: Some(spec),
+    })
+}
+
+#[cfg(not(unix))]
+async fn launch_over_pipe(
+    _command: Command,
+    _spec: LaunchSpec,
+    _is_arc: bool,
+) -> Result<ManageableBrowserInstance, RuntimeError> {
+    Err(RuntimeError::Unsupported {
+        reason: "The pipe transport is only supported on macOS and Linux".to_string(),
+    })
+}
+
+/*
+** `port` may be 0: the browser then picks a free port and reports it back
+*/
+async fn launch_over_port(
+    mut command: Command,
+    mut spec: LaunchSpec,
+    is_arc: bool,
+    port: u16,
+    user_data_dir: &Path,
+) -> Result<ManageableBrowserInstance, RuntimeError> {
+    clear_active_port_file(user_data_dir);
+
+    let browser_id = browser_id_for_path(&spec.path);
+    let mut child_process = command.spawn().map_err(|e| RuntimeError::LaunchFailed {
+        path: spec.path.clone(),
+        reason: e.to_string(),
+    })?;
+    let (logs, listening) = capture_browser_output(&mut child_process, &spec);
+
     /*
-     ** quick health-check of /json/version for clearer error reporting
+     ** ready as soon as the browser says where DevTools is listening
      */
-    let version_url = format!("http://127.0.0.1:{port}/json/version");
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
-
-    match client.get(&version_url).send().await {
-        Ok(resp) if resp.status().is_success() => { /* looks good */ }
-        Ok(_) => { /* continue – endpoint might not be ready yet */ }
+    let active = match wait_until_ready(
+        &mut child_process,
+        Some(user_data_dir),
+        listening,
+        LAUNCH_TIMEOUT,
+    )
+    .await
+    {
+        Ok(active) => active,
         Err(e) => {
             let _ = child_process.kill();
             let _ = child_process.wait();
-            return Err(format!(
-                "browser launched but debugging endpoint is not accessible. Error: {e}"
-            ));
+            return Err(e);
+        }
+    };
+
+    if port != 0 && active.port != port {
+        info!(
+            "browser reported DevTools on port {} instead of {port}",
+            active.port
+        );
+    }
+    let port = active.port;
+    let ws_url = active.ws_url;
+    let endpoint = CdpEndpoint::local(port)
+        .with_ws_url(&ws_url)
+        .unwrap_or_else(|_| CdpEndpoint::local(port));
+
+    if is_arc {
+        let url = spec
+            .options
+            .starter_page
+            .as_deref()
+            .unwrap_or("about:blank");
+        if let Err(e) = crate::network::create_new_page(&endpoint, Some(url)).await {
+            warn!("failed to open a window for Arc: {e}");
         }
     }
 
     /*
-     ** finally, fetch the WebSocket URL
+     ** a restart should come back on the port we actually got
      */
-    match get_browser_websocket_url(port, 20, 500).await {
-        Ok(ws_url) => {
-            if is_arc {
-                let _ = crate::network::create_new_page(port, Some("https://www.google.com")).await;
-            }
+    spec.transport = LaunchTransport::Port(port);
+
+    info!("browser {} is up at {ws_url}", spec.path);
+    Ok(ManageableBrowserInstance {
+        browser_id: browser_id.to_string(),
+        path: spec.path.clone(),
+        transport: DebugTransport::Tcp,
+        endpoint: Some(endpoint),
+        ws_url: Some(ws_url.clone()),
+        child: Some(child_process),
+        launched_by_app: true,
+        cdp: connect_cdp(&ws_url).await,
+        logs: Some(logs),
+        launch: Some(spec),
+    })
+}
 
-            let mut managed_browser_guard = MANAGED_BROWSER.lock().await;
-            *managed_browser_guard = Some(ManageableBrowserInstance {
-                path: target_browser_path.to_string(),
-                port,
-                ws_url: ws_url.clone(),
-                child: Some(child_process),
-                launched_by_app: true,
-            });
-            println!("browser launched successfully at {ws_url}");
-            Ok(ws_url)
+/*
+** how long each shutdown stage gets before we escalate to the next one
+*/
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+const TERM_GRACE: Duration = Duration::from_secs(3);
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+async fn wait_for_exit(child: &mut Child, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                debug!(
+                    "browser process {} exited with status: {status}",
+                    child.id()
+                );
+                return true;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("error checking browser process {}: {e}", child.id());
+                return false;
+            }
         }
-        Err(e) => {
-            let _ = child_process.kill();
-            let _ = child_process.wait();
-            Err(format!("Failed to establish debugging connection: {e}"))
+        if tokio::time::Instant::now() >= deadline {
+            return false;
         }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
     }
 }
 
+#[cfg(unix)]
+fn terminate(child: &Child) -> bool {
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
 /*
-** gracefully (or forcefully) close the managed browser, if we launched it.
+** no SIGTERM equivalent for a GUI process here; the next stage kills it
 */
-pub async fn sunset_browser_instance() -> Result<(), String> {
-    println!("attempting to close the debug browser...");
-    let mut managed_browser_guard = MANAGED_BROWSER.lock().await;
-
-    if let Some(mut instance) = managed_browser_guard.take() {
-        println!("found managed browser process to close: {}", instance.path);
-
-        if instance.launched_by_app {
-            if let Some(mut child) = instance.child.take() {
-                if child
-                    .try_wait()
-                    .map_err(|e| format!("error checking child process status: {e}"))?
-                    .is_none()
-                {
-                    if let Err(e) = child.kill() {
-                        eprintln!("failed to kill browser process {}: {}", instance.path, e);
-                    }
-                    match child.wait() {
-                        Ok(status) => println!(
-                            "browser process {} exited with status: {status}",
-                            instance.path
-                        ),
-                        Err(e) => eprintln!(
-                            "error waiting for browser process {} to exit: {}",
-                            instance.path, e
-                        ),
-                    }
-                } else {
-                    println!("browser process {} was already exited.", instance.path);
-                }
-            } else {
-                println!("no child handle stored, skipping kill.");
+#[cfg(not(unix))]
+fn terminate(_child: &Child) -> bool {
+    false
+}
+
+/*
+** staged shutdown of a browser we launched: CDP Browser.close (unless forced),
+** then SIGTERM, then kill. the instance must already be out of the registry
+** so the watchdog doesn't mistake this for a crash.
+*/
+pub async fn shutdown_browser(instance: &mut ManageableBrowserInstance, force: bool) {
+    let Some(mut child) = instance.child.take() else {
+        debug!(
+            "no child handle stored for {}, skipping shutdown.",
+            instance.path
+        );
+        return;
+    };
+    if wait_for_exit(&mut child, Duration::ZERO).await {
+        return;
+    }
+
+    if !force {
+        if let Some(cdp) = instance.cdp.take().filter(|s| s.is_connected()) {
+            let _ = tokio::time::timeout(
+                CLOSE_GRACE,
+                cdp.send("Browser.close", serde_json::json!({})),
+            )
+            .await;
+            if wait_for_exit(&mut child, CLOSE_GRACE).await {
+                return;
             }
-        } else {
-            println!("browser was not launched by app; leaving it running.");
+            warn!("browser {} ignored Browser.close", instance.path);
         }
+    }
 
-        println!(
-            "managed browser instance for {} closed and removed.",
+    if terminate(&child) && wait_for_exit(&mut child, TERM_GRACE).await {
+        return;
+    }
+
+    warn!("killing browser process {}", instance.path);
+    if let Err(e) = child.kill() {
+        error!("failed to kill browser process {}: {e}", instance.path);
+    }
+    if let Err(e) = child.wait() {
+        error!(
+            "error waiting for browser process {} to exit: {e}",
             instance.path
         );
-        Ok(())
+    }
+}
+
+/*
+** close a managed browser, if we launched it.
+*/
+pub async fn sunset_browser_instance(instance_id: Option<&str>) -> Result<(), RuntimeError> {
+    debug!("attempting to close the debug browser...");
+    let Some(TakenInstance {
+        instance_id,
+        mut instance,
+        ..
+    }) = BROWSER_MANAGER.take(instance_id, TakeIf::Any).await?
+    else {
+        debug!("no managed browser process was running to close.");
+        return Ok(());
+    };
+
+    info!(
+        "found managed browser process to close: {instance_id} ({})",
+        instance.path
+    );
+
+    if instance.launched_by_app {
+        shutdown_browser(&mut instance, false).await;
     } else {
-        println!("no managed browser process was running to close.");
-        Ok(())
+        info!("browser was not launched by app; leaving it running.");
     }
+
+    info!("managed browser instance {instance_id} closed and removed.");
+    Ok(())
+}
+
+/*
+** app is exiting: no browser we started may outlive it
+*/
+pub async fn shutdown_all_launched() {
+    let mut launched = BROWSER_MANAGER.take_launched_by_app().await;
+    if launched.is_empty() {
+        return;
+    }
+
+    info!(
+        "closing {} app-launched browser(s) before exit",
+        launched.len()
+    );
+    join_all(
+        launched
+            .iter_mut()
+            .map(|instance| shutdown_browser(instance, false)),
+    )
+    .await;
 }
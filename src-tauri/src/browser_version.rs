@@ -0,0 +1,397 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::browser_id_for_path;
+
+/*
+** the oldest engine we drive. launch presets rely on the new headless mode,
+** which replaced the old one as --headless=new in Chromium 112.
+*/
+pub const MIN_ENGINE_MAJOR: u32 = 112;
+const PROTOCOL_VERSION: &str = "1.3";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BrowserFamily {
+    Chrome,
+    Chromium,
+    Edge,
+    Brave,
+    Vivaldi,
+    Opera,
+    Arc,
+    Unknown,
+}
+
+impl BrowserFamily {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BrowserFamily::Chrome => "chrome",
+            BrowserFamily::Chromium => "chromium",
+            BrowserFamily::Edge => "edge",
+            BrowserFamily::Brave => "brave",
+            BrowserFamily::Vivaldi => "vivaldi",
+            BrowserFamily::Opera => "opera",
+            BrowserFamily::Arc => "arc",
+            BrowserFamily::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Self {
+        match id {
+            "chrome" => BrowserFamily::Chrome,
+            "chromium" => BrowserFamily::Chromium,
+            "edge" => BrowserFamily::Edge,
+            "brave" => BrowserFamily::Brave,
+            "vivaldi" => BrowserFamily::Vivaldi,
+            "opera" => BrowserFamily::Opera,
+            "arc" => BrowserFamily::Arc,
+            _ => BrowserFamily::Unknown,
+        }
+    }
+}
+
+/*
+** tokens a browser adds to its product string or user agent on top of
+** Chrome's ("HeadlessEdg/" included). checked in order.
+*/
+const FINGERPRINTS: &[(&str, BrowserFamily)] = &[
+    ("Edg/", BrowserFamily::Edge),
+    ("OPR/", BrowserFamily::Opera),
+    ("Vivaldi/", BrowserFamily::Vivaldi),
+    ("Brave/", BrowserFamily::Brave),
+    ("Arc/", BrowserFamily::Arc),
+    ("Chromium/", BrowserFamily::Chromium),
+];
+
+/*
+** what a DevTools server says about itself, from /json/version or Browser.getVersion
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct BrowserVersion {
+    pub product: String,         // "Chrome/120.0.6099.109"
+    pub name: String,            // "Chrome"
+    pub version: Option<String>, // "120.0.6099.109"
+    pub protocol_version: String,
+    pub user_agent: String,
+    pub v8_version: Option<String>,
+    pub webkit_version: Option<String>,
+    pub ws_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Compatibility {
+    pub supported: bool,
+    pub engine_major: Option<u32>,
+    pub minimum_major: u32,
+    pub issues: Vec<String>,
+}
+
+/*
+** what the UI gets for a running instance
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrowserVersionReport {
+    #[serde(flatten)]
+    pub version: BrowserVersion,
+    pub family: BrowserFamily,
+    pub compatibility: Compatibility,
+}
+
+fn text(value: &Value, key: &str) -> Option<String> {
+    value[key]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+impl BrowserVersion {
+    fn new(product: String, protocol_version: String, user_agent: String) -> Self {
+        let (name, version) = match product.split_once('/') {
+            Some((name, version)) => (name.to_string(), Some(version.to_string())),
+            None => (product.clone(), None),
+        };
+        Self {
+            product,
+            name,
+            version,
+            protocol_version,
+            user_agent,
+            ..Self::default()
+        }
+    }
+
+    /*
+     ** the /json/version payload ("Browser", "Protocol-Version", "V8-Version", ...)
+     */
+    pub fn from_json_version(payload: &Value) -> Result<Self, String> {
+        let product = text(payload, "Browser")
+            .ok_or_else(|| "DevTools version info has no \"Browser\" field".to_string())?;
+        Ok(Self {
+            v8_version: text(payload, "V8-Version"),
+            webkit_version: text(payload, "WebKit-Version"),
+            ws_url: text(payload, "webSocketDebuggerUrl"),
+            ..Self::new(
+                product,
+                text(payload, "Protocol-Version").unwrap_or_default(),
+                text(payload, "User-Agent").unwrap_or_default(),
+            )
+        })
+    }
+
+    /*
+     ** the result of Browser.getVersion, for pipe browsers without /json/version
+     */
+    pub fn from_cdp(result: &Value) -> Result<Self, String> {
+        let product = text(result, "product")
+            .ok_or_else(|| "Browser.getVersion returned no product".to_string())?;
+        Ok(Self {
+            v8_version: text(result, "jsVersion"),
+            ..Self::new(
+                product,
+                text(result, "protocolVersion").unwrap_or_default(),
+                text(result, "userAgent").unwrap_or_default(),
+            )
+        })
+    }
+
+    /*
+     ** Brave, Vivaldi and Chromium look exactly like Chrome over DevTools, so a
+     ** generic answer falls back to `hint`: an executable or user-data dir path.
+     */
+    pub fn family(&self, hint: Option<&str>) -> BrowserFamily {
+        let marked = FINGERPRINTS
+            .iter()
+            .find(|(token, _)| self.product.starts_with(token) || self.user_agent.contains(token));
+        if let Some((_, family)) = marked {
+            return *family;
+        }
+
+        let is_chrome = matches!(self.name.as_str(), "Chrome" | "HeadlessChrome")
+            || self.user_agent.contains("Chrome/");
+        match (is_chrome, hint) {
+            (true, Some(hint)) => BrowserFamily::from_id(browser_id_for_path(hint)),
+            (true, None) => BrowserFamily::Chrome,
+            (false, _) => BrowserFamily::Unknown,
+        }
+    }
+
+    /*
+     ** Chromium major version. Opera numbers its own releases, so the
+     ** "Chrome/" token of the user agent wins over the product version.
+     */
+    pub fn engine_major(&self) -> Option<u32> {
+        let from_agent = self
+            .user_agent
+            .split_whitespace()
+            .find_map(|token| token.strip_prefix("Chrome/"))
+            .or_else(|| {
+                self.user_agent
+                    .split_whitespace()
+                    .find_map(|token| token.strip_prefix("HeadlessChrome/"))
+            });
+        let from_product = matches!(
+            self.name.as_str(),
+            "Chrome" | "HeadlessChrome" | "Edg" | "HeadlessEdg"
+        )
+        .then_some(self.version.as_deref())
+        .flatten();
+
+        from_agent.or(from_product)?.split('.').next()?.parse().ok()
+    }
+
+    pub fn compatibility(&self) -> Compatibility {
+        let engine_major = self.engine_major();
+        let mut issues = Vec::new();
+
+        match engine_major {
+            Some(major) if major < MIN_ENGINE_MAJOR => issues.push(format!(
+                "{} is based on Chromium {major}; version {MIN_ENGINE_MAJOR} or newer is required",
+                self.product
+            )),
+            Some(_) => {}
+            None => issues.push(format!("{} is not a Chromium-based browser", self.product)),
+        }
+        if !self.protocol_version.is_empty() && self.protocol_version != PROTOCOL_VERSION {
+            issues.push(format!(
+                "DevTools protocol {} is not supported (expected {PROTOCOL_VERSION})",
+                self.protocol_version
+            ));
+        }
+
+        Compatibility {
+            supported: issues.is_empty(),
+            engine_major,
+            minimum_major: MIN_ENGINE_MAJOR,
+            issues,
+        }
+    }
+
+    pub fn report(self, hint: Option<&str>) -> BrowserVersionReport {
+        BrowserVersionReport {
+            family: self.family(hint),
+            compatibility: self.compatibility(),
+            version: self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CHROME_UA: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+    fn payload(browser: &str, user_agent: &str) -> BrowserVersion {
+        BrowserVersion::from_json_version(&json!({
+            "Browser": browser,
+            "Protocol-Version": "1.3",
+            "User-Agent": user_agent,
+            "V8-Version": "12.0.267.8",
+            "WebKit-Version": "537.36 (@6a1600ed572fedecd573b6c2b90a22fe6392a410)",
+            "webSocketDebuggerUrl": "ws://127.0.0.1:9222/devtools/browser/abc"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_json_version() {
+        let version = payload("Chrome/120.0.6099.109", CHROME_UA);
+        assert_eq!(version.name, "Chrome");
+        assert_eq!(version.version.as_deref(), Some("120.0.6099.109"));
+        assert_eq!(version.protocol_version, "1.3");
+        assert_eq!(version.v8_version.as_deref(), Some("12.0.267.8"));
+        assert!(version.webkit_version.unwrap().starts_with("537.36"));
+        assert_eq!(
+            version.ws_url.as_deref(),
+            Some("ws://127.0.0.1:9222/devtools/browser/abc")
+        );
+    }
+
+    #[test]
+    fn rejects_payload_without_product() {
+        assert!(BrowserVersion::from_json_version(&json!({ "User-Agent": CHROME_UA })).is_err());
+    }
+
+    #[test]
+    fn parses_cdp_get_version() {
+        let version = BrowserVersion::from_cdp(&json!({
+            "protocolVersion": "1.3",
+            "product": "HeadlessChrome/121.0.6167.85",
+            "revision": "@1a2b3c",
+            "userAgent": "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/121.0.0.0 Safari/537.36",
+            "jsVersion": "12.1.285.20"
+        }))
+        .unwrap();
+        assert_eq!(version.name, "HeadlessChrome");
+        assert_eq!(version.v8_version.as_deref(), Some("12.1.285.20"));
+        assert_eq!(version.family(None), BrowserFamily::Chrome);
+        assert_eq!(version.engine_major(), Some(121));
+    }
+
+    #[test]
+    fn classifies_by_fingerprint() {
+        let cases = [
+            (
+                "Chrome/120.0.6099.109",
+                CHROME_UA.to_string(),
+                BrowserFamily::Chrome,
+            ),
+            (
+                "Edg/120.0.2210.91",
+                format!("{CHROME_UA} Edg/120.0.0.0"),
+                BrowserFamily::Edge,
+            ),
+            (
+                "Chrome/119.0.6045.199",
+                format!("{CHROME_UA} OPR/105.0.0.0"),
+                BrowserFamily::Opera,
+            ),
+            (
+                "Chrome/120.0.6099.225",
+                format!("{CHROME_UA} Vivaldi/6.5.3206.53"),
+                BrowserFamily::Vivaldi,
+            ),
+            (
+                "Chrome/120.0.6099.109",
+                CHROME_UA.replace("Chrome/", "Chromium/120.0.0.0 Chrome/"),
+                BrowserFamily::Chromium,
+            ),
+            (
+                "Chrome/120.0.6099.109",
+                format!("{CHROME_UA} Arc/1.21.1"),
+                BrowserFamily::Arc,
+            ),
+            (
+                "Firefox/121.0",
+                "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0"
+                    .to_string(),
+                BrowserFamily::Unknown,
+            ),
+        ];
+        for (product, user_agent, family) in cases {
+            assert_eq!(
+                payload(product, &user_agent).family(None),
+                family,
+                "{product}"
+            );
+        }
+    }
+
+    #[test]
+    fn brave_is_chrome_without_a_hint() {
+        let version = payload("Chrome/120.0.6099.109", CHROME_UA);
+        assert_eq!(version.family(None), BrowserFamily::Chrome);
+        assert_eq!(
+            version.family(Some("/usr/bin/brave-browser")),
+            BrowserFamily::Brave
+        );
+        assert_eq!(
+            version.family(Some("/home/me/.config/chromium")),
+            BrowserFamily::Chromium
+        );
+    }
+
+    #[test]
+    fn fingerprint_beats_hint() {
+        let version = payload("Edg/120.0.2210.91", &format!("{CHROME_UA} Edg/120.0.0.0"));
+        assert_eq!(
+            version.family(Some("/usr/bin/google-chrome")),
+            BrowserFamily::Edge
+        );
+    }
+
+    #[test]
+    fn engine_version_of_opera_comes_from_chrome_token() {
+        let opera = payload(
+            "Opera/105.0.4970.16",
+            &(CHROME_UA.replace("Chrome/120", "Chrome/119") + " OPR/105.0.0.0"),
+        );
+        assert_eq!(opera.engine_major(), Some(119));
+    }
+
+    #[test]
+    fn compatibility() {
+        let current = payload("Chrome/120.0.6099.109", CHROME_UA).compatibility();
+        assert!(current.supported);
+        assert_eq!(current.engine_major, Some(120));
+
+        let old = payload(
+            "Chrome/99.0.4844.51",
+            &CHROME_UA.replace("Chrome/120", "Chrome/99"),
+        )
+        .compatibility();
+        assert!(!old.supported);
+        assert_eq!(old.minimum_major, MIN_ENGINE_MAJOR);
+        assert!(old.issues[0].contains("Chromium 99"));
+
+        let firefox = payload(
+            "Firefox/121.0",
+            "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0",
+        )
+        .compatibility();
+        assert!(!firefox.supported);
+        assert_eq!(firefox.engine_major, None);
+    }
+}
@@ -0,0 +1,847 @@
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::error::RuntimeError;
+#[cfg(unix)]
+use crate::pipe::DebugPipe;
+use tracing::{debug, warn};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+const EVENT_CHANNEL_CAPACITY: usize = 512;
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/*
+** a CDP command paired with the shape of its result
+*/
+pub trait CdpCommand: Serialize {
+    const METHOD: &'static str;
+    type Response: DeserializeOwned;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CdpEvent {
+    pub method: String,
+    pub params: Value,
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IncomingMessage {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<ProtocolError>,
+    method: Option<String>,
+    params: Option<Value>,
+    session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtocolError {
+    code: i64,
+    message: String,
+}
+
+type PendingMap = HashMap<u64, oneshot::Sender<Result<Value, String>>>;
+
+struct Inner {
+    id: u64,
+    next_id: AtomicU64,
+    outgoing: mpsc::UnboundedSender<String>,
+    pending: Mutex<PendingMap>,
+    events: broadcast::Sender<CdpEvent>,
+    closed: AtomicBool,
+}
+
+impl Inner {
+    /*
+     ** route one raw protocol message: responses go to their waiting caller,
+     ** everything else is an event and gets broadcast.
+     */
+    fn dispatch(&self, text: &str) {
+        let message: IncomingMessage = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("cdp: dropping malformed message: {e}");
+                return;
+            }
+        };
+
+        if let Some(id) = message.id {
+            let waiter = self.pending.lock().unwrap().remove(&id);
+            if let Some(waiter) = waiter {
+                let result = match message.error {
+                    Some(err) => Err(format!("{} (code {})", err.message, err.code)),
+                    None => Ok(message.result.unwrap_or(Value::Null)),
+                };
+                let _ = waiter.send(result);
+            }
+        } else if let Some(method) = message.method {
+            let _ = self.events.send(CdpEvent {
+                method,
+                params: message.params.unwrap_or(Value::Null),
+                session_id: message.session_id,
+            });
+        }
+    }
+
+    /*
+     ** fail every in-flight command once the transport is gone
+     */
+    fn shutdown(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+/*
+** cheap, cloneable handle on one DevTools connection.
+** the socket stays open as long as at least one handle is alive.
+*/
+#[derive(Clone)]
+pub struct CdpSession {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for CdpSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CdpSession")
+            .field("connected", &self.is_connected())
+            .field("pending", &self.inner.pending.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl CdpSession {
+    pub async fn connect(ws_url: &str) -> Result<Self, RuntimeError> {
+        let (stream, _) =
+            connect_async(ws_url)
+                .await
+                .map_err(|e| RuntimeError::CdpUnavailable {
+                    reason: format!("Failed to open DevTools websocket {ws_url}: {e}"),
+                })?;
+        let (mut sink, mut source) = stream.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        let session = Self::with_outgoing(outgoing);
+
+        /*
+         ** writer: ends when every handle (and so every sender) is dropped
+         */
+        tokio::spawn(async move {
+            while let Some(text) = outgoing_rx.recv().await {
+                if sink.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        /*
+         ** reader: holds a weak ref so it never keeps the session alive on its own
+         */
+        let weak: Weak<Inner> = Arc::downgrade(&session.inner);
+        tokio::spawn(async move {
+            while let Some(frame) = source.next().await {
+                let text = match frame {
+                    Ok(Message::Text(text)) => text.to_string(),
+                    Ok(Message::Close(_)) | Err(_) => break,
+                    Ok(_) => continue,
+                };
+                match weak.upgrade() {
+                    Some(inner) => inner.dispatch(&text),
+                    None => break,
+                }
+            }
+            if let Some(inner) = weak.upgrade() {
+                inner.shutdown();
+            }
+            debug!("cdp: websocket closed");
+        });
+
+        debug!("cdp: connected to {ws_url}");
+        Ok(session)
+    }
+
+    /*
+     ** speak CDP over the fds of a browser launched with --remote-debugging-pipe.
+     ** messages are NUL-terminated JSON; the pipe ends are blocking, so they get
+     ** their own threads instead of tokio tasks.
+     */
+    #[cfg(unix)]
+    pub fn connect_pipe(pipe: DebugPipe) -> Self {
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        let session = Self::with_outgoing(outgoing);
+        let DebugPipe { mut writer, reader } = pipe;
+
+        std::thread::spawn(move || {
+            while let Some(text) = outgoing_rx.blocking_recv() {
+                let sent = writer
+                    .write_all(text.as_bytes())
+                    .and_then(|_| writer.write_all(b"\0"));
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let weak: Weak<Inner> = Arc::downgrade(&session.inner);
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut buffer = Vec::new();
+            loop {
+                buffer.clear();
+                match reader.read_until(b'\0', &mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                if buffer.last() == Some(&b'\0') {
+                    buffer.pop();
+                }
+                match weak.upgrade() {
+                    Some(inner) => inner.dispatch(&String::from_utf8_lossy(&buffer)),
+                    None => break,
+                }
+            }
+            if let Some(inner) = weak.upgrade() {
+                inner.shutdown();
+            }
+            debug!("cdp: debugging pipe closed");
+        });
+
+        session
+    }
+
+    fn with_outgoing(outgoing: mpsc::UnboundedSender<String>) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            inner: Arc::new(Inner {
+                id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+                next_id: AtomicU64::new(1),
+                outgoing,
+                pending: Mutex::new(HashMap::new()),
+                events,
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /*
+     ** unique per connection, so a reconnect is told apart from the session it replaced
+     */
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !self.inner.closed.load(Ordering::SeqCst) && !self.inner.outgoing.is_closed()
+    }
+
+    /*
+     ** every event from the browser and from all flattened target sessions
+     */
+    pub fn subscribe(&self) -> broadcast::Receiver<CdpEvent> {
+        self.inner.events.subscribe()
+    }
+
+    pub async fn send(&self, method: &str, params: Value) -> Result<Value, RuntimeError> {
+        self.send_raw(None, method, params).await
+    }
+
+    pub async fn send_to(
+        &self,
+        session_id: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RuntimeError> {
+        self.send_raw(Some(session_id), method, params).await
+    }
+
+    pub async fn execute<C: CdpCommand>(&self, command: &C) -> Result<C::Response, RuntimeError> {
+        self.execute_raw(None, command).await
+    }
+
+    pub async fn execute_in<C: CdpCommand>(
+        &self,
+        session_id: &str,
+        command: &C,
+    ) -> Result<C::Response, RuntimeError> {
+        self.execute_raw(Some(session_id), command).await
+    }
+
+    async fn execute_raw<C: CdpCommand>(
+        &self,
+        session_id: Option<&str>,
+        command: &C,
+    ) -> Result<C::Response, RuntimeError> {
+        let params = serde_json::to_value(command)
+            .map_err(|e| RuntimeError::cdp(format!("Failed to encode {}: {e}", C::METHOD)))?;
+        let result = self.send_raw(session_id, C::METHOD, params).await?;
+        serde_json::from_value(result)
+            .map_err(|e| RuntimeError::cdp(format!("Failed to decode {} response: {e}", C::METHOD)))
+    }
+
+    async fn send_raw(
+        &self,
+        session_id: Option<&str>,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RuntimeError> {
+        if !self.is_connected() {
+            return Err(RuntimeError::cdp(format!(
+                "Cannot send {method}: CDP connection is closed"
+            )));
+        }
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut message = json!({ "id": id, "method": method, "params": params });
+        if let Some(session_id) = session_id {
+            message["sessionId"] = json!(session_id);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.inner.pending.lock().unwrap().insert(id, tx);
+
+        if self.inner.outgoing.send(message.to_string()).is_err() {
+            self.inner.pending.lock().unwrap().remove(&id);
+            return Err(RuntimeError::cdp(format!(
+                "Cannot send {method}: CDP connection is closed"
+            )));
+        }
+
+        let result = match tokio::time::timeout(COMMAND_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result.map_err(|e| format!("{method} failed: {e}")),
+            Ok(Err(_)) => Err(format!("CDP connection closed while waiting for {method}")),
+            Err(_) => {
+                self.inner.pending.lock().unwrap().remove(&id);
+                Err(format!(
+                    "{method} timed out after {}s",
+                    COMMAND_TIMEOUT.as_secs()
+                ))
+            }
+        };
+        result.map_err(RuntimeError::cdp)
+    }
+
+    /*
+     ** attach with flatten=true so the target is driven over this same socket
+     */
+    pub async fn attach_to_target(&self, target_id: &str) -> Result<TargetSession, RuntimeError> {
+        let attached = self
+            .execute(&AttachToTarget {
+                target_id: target_id.to_string(),
+                flatten: true,
+            })
+            .await?;
+
+        Ok(TargetSession {
+            browser: self.clone(),
+            session_id: attached.session_id,
+        })
+    }
+}
+
+/*
+** a flattened session on a single target (page, worker, ...)
+*/
+#[derive(Debug, Clone)]
+pub struct TargetSession {
+    browser: CdpSession,
+    pub session_id: String,
+}
+
+impl TargetSession {
+    pub async fn execute<C: CdpCommand>(&self, command: &C) -> Result<C::Response, RuntimeError> {
+        self.browser.execute_in(&self.session_id, command).await
+    }
+
+    pub async fn detach(self) -> Result<(), RuntimeError> {
+        self.browser
+            .execute(&DetachFromTarget {
+                session_id: self.session_id,
+            })
+            .await
+            .map(|_| ())
+    }
+}
+
+/*
+** typed commands
+*/
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachToTarget {
+    pub target_id: String,
+    pub flatten: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachToTargetResponse {
+    pub session_id: String,
+}
+
+impl CdpCommand for AttachToTarget {
+    const METHOD: &'static str = "Target.attachToTarget";
+    type Response = AttachToTargetResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetachFromTarget {
+    pub session_id: String,
+}
+
+impl CdpCommand for DetachFromTarget {
+    const METHOD: &'static str = "Target.detachFromTarget";
+    type Response = Value;
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetTargets {}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetInfo {
+    pub target_id: String,
+    #[serde(rename = "type")]
+    pub target_type: String,
+    pub title: String,
+    pub url: String,
+    pub attached: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTargetsResponse {
+    pub target_infos: Vec<TargetInfo>,
+}
+
+impl CdpCommand for GetTargets {
+    const METHOD: &'static str = "Target.getTargets";
+    type Response = GetTargetsResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTarget {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTargetResponse {
+    pub target_id: String,
+}
+
+impl CdpCommand for CreateTarget {
+    const METHOD: &'static str = "Target.createTarget";
+    type Response = CreateTargetResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivateTarget {
+    pub target_id: String,
+}
+
+impl CdpCommand for ActivateTarget {
+    const METHOD: &'static str = "Target.activateTarget";
+    type Response = Value;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseTarget {
+    pub target_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloseTargetResponse {
+    #[serde(default = "closed")]
+    pub success: bool,
+}
+
+/*
+** newer browsers dropped `success` from the result and just report errors
+*/
+fn closed() -> bool {
+    true
+}
+
+impl CdpCommand for CloseTarget {
+    const METHOD: &'static str = "Target.closeTarget";
+    type Response = CloseTargetResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reload {
+    pub ignore_cache: bool,
+}
+
+impl CdpCommand for Reload {
+    const METHOD: &'static str = "Page.reload";
+    type Response = Value;
+}
+
+/*
+** a region of the page in CSS pixels; `scale` resizes the captured image
+*/
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub scale: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureScreenshot {
+    pub format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip: Option<Viewport>,
+    pub capture_beyond_viewport: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaptureScreenshotResponse {
+    /*
+     ** base64 image bytes
+     */
+    pub data: String,
+}
+
+impl CdpCommand for CaptureScreenshot {
+    const METHOD: &'static str = "Page.captureScreenshot";
+    type Response = CaptureScreenshotResponse;
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetLayoutMetrics {}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VisualViewport {
+    pub page_x: f64,
+    pub page_y: f64,
+    pub client_width: f64,
+    pub client_height: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLayoutMetricsResponse {
+    pub css_visual_viewport: VisualViewport,
+    pub css_content_size: ContentSize,
+}
+
+impl CdpCommand for GetLayoutMetrics {
+    const METHOD: &'static str = "Page.getLayoutMetrics";
+    type Response = GetLayoutMetricsResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluate {
+    pub expression: String,
+    pub return_by_value: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteObject {
+    pub value: Option<Value>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExceptionDetails {
+    pub text: String,
+    pub exception: Option<RemoteObject>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateResponse {
+    pub result: RemoteObject,
+    pub exception_details: Option<ExceptionDetails>,
+}
+
+impl EvaluateResponse {
+    /*
+     ** the thrown error's description when there is one, it names the error type
+     */
+    pub fn into_value(self) -> Result<Value, String> {
+        match self.exception_details {
+            Some(details) => Err(details
+                .exception
+                .and_then(|exception| exception.description)
+                .unwrap_or(details.text)),
+            None => Ok(self.result.value.unwrap_or(Value::Null)),
+        }
+    }
+}
+
+impl CdpCommand for Evaluate {
+    const METHOD: &'static str = "Runtime.evaluate";
+    type Response = EvaluateResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTargetInfo {
+    pub target_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTargetInfoResponse {
+    pub target_info: TargetInfo,
+}
+
+impl CdpCommand for GetTargetInfo {
+    const METHOD: &'static str = "Target.getTargetInfo";
+    type Response = GetTargetInfoResponse;
+}
+
+/*
+** sizes and margins are in inches
+*/
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintToPdf {
+    pub landscape: bool,
+    pub display_header_footer: bool,
+    pub print_background: bool,
+    pub scale: f64,
+    pub paper_width: f64,
+    pub paper_height: f64,
+    pub margin_top: f64,
+    pub margin_bottom: f64,
+    pub margin_left: f64,
+    pub margin_right: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_ranges: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer_template: Option<String>,
+    #[serde(rename = "preferCSSPageSize")]
+    pub prefer_css_page_size: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrintToPdfResponse {
+    /*
+     ** base64 PDF bytes
+     */
+    pub data: String,
+}
+
+impl CdpCommand for PrintToPdf {
+    const METHOD: &'static str = "Page.printToPDF";
+    type Response = PrintToPdfResponse;
+}
+
+/*
+** with allowAndName every file is saved as `<download path>/<guid>`; the
+** suggested name comes with Browser.downloadWillBegin
+*/
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDownloadBehavior {
+    pub behavior: &'static str,
+    pub download_path: String,
+    pub events_enabled: bool,
+}
+
+impl CdpCommand for SetDownloadBehavior {
+    const METHOD: &'static str = "Browser.setDownloadBehavior";
+    type Response = Value;
+}
+
+/*
+** depth 0 is enough to get the root's node id for DOM.getOuterHTML
+*/
+#[derive(Debug, Serialize)]
+pub struct GetDocument {
+    pub depth: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DomNode {
+    pub node_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetDocumentResponse {
+    pub root: DomNode,
+}
+
+impl CdpCommand for GetDocument {
+    const METHOD: &'static str = "DOM.getDocument";
+    type Response = GetDocumentResponse;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetOuterHtml {
+    pub node_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetOuterHtmlResponse {
+    pub outer_html: String,
+}
+
+impl CdpCommand for GetOuterHtml {
+    const METHOD: &'static str = "DOM.getOuterHTML";
+    type Response = GetOuterHtmlResponse;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     ** a session without a socket: what it sends comes out of the receiver,
+     ** what the "browser" says goes in through dispatch
+     */
+    fn mock_session() -> (CdpSession, mpsc::UnboundedReceiver<String>) {
+        let (outgoing, sent) = mpsc::unbounded_channel();
+        (CdpSession::with_outgoing(outgoing), sent)
+    }
+
+    async fn next_sent(sent: &mut mpsc::UnboundedReceiver<String>) -> Value {
+        serde_json::from_str(&sent.recv().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn responses_find_their_command_by_id() {
+        let (session, mut sent) = mock_session();
+        let first = tokio::spawn({
+            let session = session.clone();
+            async move { session.send("Browser.getVersion", json!({})).await }
+        });
+        let first_sent = next_sent(&mut sent).await;
+        let second = tokio::spawn({
+            let session = session.clone();
+            async move {
+                session
+                    .send_to("PAGE", "Runtime.evaluate", json!({ "expression": "1" }))
+                    .await
+            }
+        });
+        let second_sent = next_sent(&mut sent).await;
+
+        assert_eq!(first_sent["method"], "Browser.getVersion");
+        assert_eq!(second_sent["sessionId"], "PAGE");
+        assert_ne!(first_sent["id"], second_sent["id"]);
+
+        /*
+         ** answered out of order, and a stray id is ignored
+         */
+        session
+            .inner
+            .dispatch(&json!({ "id": 999, "result": {} }).to_string());
+        session.inner.dispatch(
+            &json!({ "id": second_sent["id"], "error": { "code": -32000, "message": "boom" } })
+                .to_string(),
+        );
+        session.inner.dispatch(
+            &json!({ "id": first_sent["id"], "result": { "product": "Chrome/126" } }).to_string(),
+        );
+
+        assert_eq!(
+            first.await.unwrap().unwrap(),
+            json!({ "product": "Chrome/126" })
+        );
+        match second.await.unwrap() {
+            Err(RuntimeError::CdpCommandFailed { reason }) => {
+                assert!(reason.contains("Runtime.evaluate failed: boom (code -32000)"))
+            }
+            other => panic!("expected CdpCommandFailed, got {other:?}"),
+        }
+        assert!(session.inner.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn events_reach_every_subscriber() {
+        let (session, _sent) = mock_session();
+        let mut first = session.subscribe();
+        let mut second = session.subscribe();
+
+        session.inner.dispatch("not json");
+        session.inner.dispatch(
+            &json!({
+                "method": "Page.loadEventFired",
+                "params": { "timestamp": 1.5 },
+                "sessionId": "PAGE",
+            })
+            .to_string(),
+        );
+
+        for events in [&mut first, &mut second] {
+            let event = events.recv().await.unwrap();
+            assert_eq!(event.method, "Page.loadEventFired");
+            assert_eq!(event.params["timestamp"], 1.5);
+            assert_eq!(event.session_id.as_deref(), Some("PAGE"));
+            assert!(events.try_recv().is_err());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unanswered_commands_time_out() {
+        let (session, _sent) = mock_session();
+        match session.send("Page.navigate", json!({})).await {
+            Err(RuntimeError::CdpCommandFailed { reason }) => {
+                assert_eq!(reason, "Page.navigate timed out after 30s")
+            }
+            other => panic!("expected a timeout, got {other:?}"),
+        }
+        assert!(session.inner.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_closed_transport_fails_commands_in_flight() {
+        let (session, mut sent) = mock_session();
+        let waiting = tokio::spawn({
+            let session = session.clone();
+            async move { session.send("Page.enable", json!({})).await }
+        });
+        next_sent(&mut sent).await;
+        session.inner.shutdown();
+
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(RuntimeError::CdpCommandFailed { .. })
+        ));
+        assert!(!session.is_connected());
+        assert!(session.send("Page.enable", json!({})).await.is_err());
+    }
+}
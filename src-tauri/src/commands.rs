@@ -1,254 +1,448 @@
 use crate::apps::call;
-use crate::browser_manager::{launch_new_instance, sunset_browser_instance, MANAGED_BROWSER};
-use crate::network::{
-    create_new_page, determine_browser_type, extract_port_from_ws_url, find_free_port,
-    get_browser_info, get_browser_websocket_url, scan_for_existing_browser_instances,
+use crate::artifacts::{self, Artifact};
+use crate::browser_logs::{BrowserLogLine, LAST_LAUNCH_LOGS};
+use crate::browser_manager::{
+    adopt_external_instance, launch_new_instance, managed_cdp_session, shutdown_browser,
+    sunset_browser_instance, LaunchSpec, LaunchTransport,
+};
+use crate::browser_version::{BrowserFamily, BrowserVersion, BrowserVersionReport};
+use crate::custom_browsers::{self, available_browsers, find_browser_by_path, rescan_browsers};
+use crate::documents::{self, ExtractedDocument};
+use crate::downloads::{self, Download};
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+use crate::http::{http_settings, save_http_settings, HttpSettings};
+use crate::launch_options::LaunchOptions;
+use crate::logging::{self, LogLevel, LogQuery, LogRecord};
+use crate::network::{find_free_port, get_browser_info, get_browser_websocket_url, is_port_free};
+use crate::port_policy::{port_policy, save_port_policy, PortPolicy};
+use crate::printing::{self, PdfOptions};
+use crate::profiles;
+use crate::readability::{self, PageMarkdown};
+use crate::registry::{TakeIf, TakenInstance, BROWSER_MANAGER};
+use crate::scanner::{scan_for_endpoints, scan_for_existing_browser_instances};
+use crate::screenshots::{self, Screenshot, ScreenshotOptions};
+use crate::sketchs::{
+    BrowserConfig, BrowserInstanceInfo, BrowserProfileInfo, BrowserTarget, DebugTransport,
+    DiscoveredEndpoint, LaunchedInstance,
 };
-use crate::platform::detect_browsers;
-use crate::sketchs::{BrowserConfig, ManageableBrowserInstance};
 use crate::sketchs_browser::WebsiteSkills;
 use crate::skills::download_skill_json;
+use crate::targets;
+use crate::utils::browser_id_for_path;
+use crate::watchdog::{RestartPolicy, RESTART_POLICY};
+use std::path::Path;
+use tracing::{debug, info, warn};
 
-const CHROME_PORT: u16 = 9522;
-const EDGE_PORT:   u16 = 9523;
-const ARC_PORT:    u16 = 9524;
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn fetch_available_browsers() -> Result<Vec<BrowserConfig>, RuntimeError> {
+    rescan_browsers().await;
+    let browsers = available_browsers().await;
 
-fn browser_id_for_path(path: &str) -> &'static str {
-    let lower = path.to_lowercase();
-    if lower.contains("edge") || lower.contains("msedge") {
-        "edge"
-    } else if lower.contains("arc") {
-        "arc"
-    } else {
-        "chrome"
-    }
-}
-fn static_port_for(id: &str) -> u16 {
-    match id {
-        "edge" => EDGE_PORT,
-        "arc"  => ARC_PORT,
-        _      => CHROME_PORT,
+    if browsers.is_empty() {
+        return Err(RuntimeError::NoBrowserAvailable);
     }
+
+    Ok(browsers)
 }
 
-fn is_equivalent_selection(selected: &str, running: &str) -> bool {
-    if selected == running { return true; }
-    selected == "arc" && running == "chrome"
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn list_custom_browsers() -> Result<Vec<BrowserConfig>, RuntimeError> {
+    Ok(custom_browsers::custom_browsers())
 }
 
+/*
+** the binary is started headless once to make sure it speaks CDP
+*/
 #[tauri::command]
-pub async fn fetch_available_browsers() -> Result<Vec<BrowserConfig>, String> {
-    let browsers = detect_browsers();
+#[tracing::instrument(skip_all, err)]
+pub async fn add_custom_browser(browser: BrowserConfig) -> Result<BrowserConfig, RuntimeError> {
+    custom_browsers::add_custom_browser(browser).await
+}
 
-    if browsers.is_empty() {
-        return Err(
-            "No browsers found. Please install Google Chrome, Microsoft Edge, or Arc".to_string(),
-        );
-    }
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn update_custom_browser(
+    id: String,
+    browser: BrowserConfig,
+) -> Result<BrowserConfig, RuntimeError> {
+    custom_browsers::update_custom_browser(&id, browser).await
+}
 
-    Ok(browsers)
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn remove_custom_browser(id: String) -> Result<(), RuntimeError> {
+    custom_browsers::remove_custom_browser(&id)
 }
 
 #[tauri::command]
-pub async fn validate_connection(ws_endpoint: String, selected_browser_path: String) -> Result<String, String> {
-    let port = extract_port_from_ws_url(&ws_endpoint)?;
-    let (browser_string, user_agent) = get_browser_info(&port).await?;
-    let running_browser_type = determine_browser_type(&browser_string, &user_agent);
+#[tracing::instrument(skip_all, err)]
+pub async fn validate_connection(
+    ws_endpoint: String,
+    selected_browser_path: String,
+) -> Result<String, RuntimeError> {
+    let endpoint = CdpEndpoint::parse(&ws_endpoint)?;
+    let version = get_browser_info(&endpoint).await?;
 
-    let browsers = detect_browsers();
+    let browsers = available_browsers().await;
     let selected_browser = browsers
         .iter()
         .find(|b| b.path == selected_browser_path)
-        .ok_or_else(|| "Selected browser not found in available browsers".to_string())?;
-
-    let selected_id = selected_browser.id.as_str();
-
-    if is_equivalent_selection(selected_id, &running_browser_type) {
+        .ok_or_else(|| RuntimeError::BrowserNotFound {
+            path: selected_browser_path.clone(),
+        })?;
+
+    let selected_id = selected_browser.family();
+    let running_family = version.family(Some(&selected_browser.path));
+    let running_browser_type = running_family.as_str();
+
+    if running_family == BrowserFamily::from_id(selected_id) {
+        let compatibility = version.compatibility();
+        let warning = if compatibility.supported {
+            String::new()
+        } else {
+            format!(" — warning: {}", compatibility.issues.join("; "))
+        };
         Ok(format!(
-            "Browser validation passed: {} (reported as {})",
+            "Browser validation passed: {} (reported as {}){warning}",
             selected_id, running_browser_type
         ))
     } else {
-        Err(format!(
-            "Browser mismatch! Selected: {}, Running: {}",
-            selected_id, running_browser_type
-        ))
+        Err(RuntimeError::BrowserMismatch {
+            selected: selected_id.to_string(),
+            running: running_browser_type.to_string(),
+        })
     }
 }
 
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
 pub async fn validate_ws_endpoint(
     ws_endpoint: String,
     selected_browser_path: String,
-) -> Result<String, String> {
-    println!("validating saved endpoint: {}", ws_endpoint); // debug print
-
-    let port = extract_port_from_ws_url(&ws_endpoint)?;
+    instance_id: Option<String>,
+) -> Result<String, RuntimeError> {
+    debug!(%ws_endpoint, "validating saved endpoint");
+
+    if let Some(instance_id) = instance_id.as_deref() {
+        let instance = BROWSER_MANAGER.snapshot(Some(instance_id)).await?;
+        if instance.ws_url.as_deref() != Some(ws_endpoint.as_str()) {
+            return Err(RuntimeError::InvalidEndpoint {
+                input: ws_endpoint,
+                reason: format!("does not belong to browser instance {instance_id}"),
+            });
+        }
+    }
 
-    match get_browser_info(&port).await {
-        Ok((browser_string, user_agent)) => {
-            let running_browser_type = determine_browser_type(&browser_string, &user_agent);
+    let endpoint = CdpEndpoint::parse(&ws_endpoint)?;
 
-            let browsers = detect_browsers();
+    match get_browser_info(&endpoint).await {
+        Ok(version) => {
+            let browsers = available_browsers().await;
             let selected_browser = browsers
                 .iter()
                 .find(|b| b.path == selected_browser_path)
-                .ok_or_else(|| "selected browser not found in available browsers".to_string())?;
-
-            let selected_id = selected_browser.id.as_str();
-
-            if is_equivalent_selection(selected_id, &running_browser_type) {
-                println!("saved endpoint is valid and matches selected browser");
+                .ok_or_else(|| RuntimeError::BrowserNotFound {
+                    path: selected_browser_path.clone(),
+                })?;
+
+            let selected_id = selected_browser.family();
+            let running_family = version.family(Some(&selected_browser.path));
+            let running_browser_type = running_family.as_str();
+
+            if running_family == BrowserFamily::from_id(selected_id) {
+                debug!("saved endpoint is valid and matches selected browser");
+                let instance_id = adopt_external_instance(
+                    &selected_browser_path,
+                    selected_id,
+                    endpoint,
+                    &ws_endpoint,
+                )
+                .await;
                 Ok(format!(
-                    "reconnected to existing {} instance (reported as {})",
-                    selected_id, running_browser_type
+                    "reconnected to existing {} instance {} (reported as {})",
+                    selected_id, instance_id, running_browser_type
                 ))
             } else {
-                Err(format!(
-                    "browser mismatch! selected: {}, running: {}",
-                    selected_id, running_browser_type
-                ))
+                Err(RuntimeError::BrowserMismatch {
+                    selected: selected_id.to_string(),
+                    running: running_browser_type.to_string(),
+                })
             }
         }
         Err(e) => {
-            println!("saved endpoint is no longer valid: {}", e);
-            Err(format!(
-                "saved browser connection is no longer available: {}",
-                e
-            ))
+            info!("saved endpoint is no longer valid: {}", e);
+            Err(e)
         }
     }
 }
 
+/*
+** `transport` defaults to the private debugging pipe; pass "tcp" when something
+** else (e.g. puppeteer in the webview) needs a websocket endpoint.
+** `log_to_file` also writes the browser's output to `<app data>/browser-logs`.
+*/
 #[tauri::command]
-pub async fn launch_browser(browser_path: Option<String>) -> Result<String, String> {
+#[tracing::instrument(skip_all, fields(browser_path = browser_path.as_deref()), err)]
+pub async fn launch_browser(
+    browser_path: Option<String>,
+    transport: Option<DebugTransport>,
+    log_to_file: Option<bool>,
+    profile: Option<String>,
+    options: Option<LaunchOptions>,
+) -> Result<LaunchedInstance, RuntimeError> {
+    let log_to_file = log_to_file.unwrap_or(false);
     let target_browser_path = if let Some(p) = browser_path {
         p
     } else {
-        fetch_available_browsers()
-            .await?
+        available_browsers()
+            .await
             .first()
             .map(|b| b.path.clone())
-            .ok_or_else(|| "No browser found".to_string())?
+            .ok_or(RuntimeError::NoBrowserAvailable)?
     };
 
-    let selected_id = browser_id_for_path(&target_browser_path);
-    let port = static_port_for(selected_id);
+    /*
+     ** a second browser on the same user-data dir would just hand over to the
+     ** first; the reservation also turns away a second launch while this one runs
+     */
+    let profile_name = profile
+        .clone()
+        .unwrap_or_else(|| browser_id_for_path(&target_browser_path).to_string());
+    let _reservation = BROWSER_MANAGER.reserve_profile(&profile_name).await?;
 
     /*
-     ** close any managed instance to free the static port.
+     ** a custom browser's own args go first so per-launch args can override them
      */
-    {
-        let mut guard = MANAGED_BROWSER.lock().await;
-        if let Some(mut inst) = guard.take() {
-            if inst.path != target_browser_path || inst.port != port {
-                if let Some(mut child) = inst.child.take() {
-                    let _ = child.kill();
-                    let _ = child.wait();
-                }
-            } else {
-                *guard = Some(inst);
-            }
-        }
+    let mut options = options.unwrap_or_default();
+    if let Some(browser) = find_browser_by_path(&target_browser_path).await {
+        options.extra_args.splice(0..0, browser.extra_args);
+    }
+    let starter_page = options.starter_page.clone();
+
+    if transport.unwrap_or_default() == DebugTransport::Pipe {
+        let launched = launch_new_instance(LaunchSpec {
+            path: target_browser_path,
+            transport: LaunchTransport::Pipe,
+            log_to_file,
+            profile,
+            options,
+        })
+        .await?;
+        return Ok(open_starter_page(launched, starter_page.as_deref()).await);
     }
 
+    let selected_id = browser_id_for_path(&target_browser_path);
+    let policy = port_policy();
+    let preferred = policy.preferred_port(selected_id);
+    let preferred_endpoint = CdpEndpoint::local(preferred);
+
     /*
-     ** probe the static port (is anything already listening?)
+     ** forget a dead instance on the preferred port; a live one of another browser keeps it.
      */
-    match get_browser_info(&port.to_string()).await {
-        Ok((browser_string, user_agent)) => {
-            let running = determine_browser_type(&browser_string, &user_agent);
-            if is_equivalent_selection(selected_id, &running) {
-                /*
-                 ** reuse existing instance on the static port
-                 */
-                let ws_url = get_browser_websocket_url(port, 20, 500)
-                    .await
-                    .map_err(|e| format!("Failed to obtain DevTools websocket: {e}"))?;
+    let mut conflict = BROWSER_MANAGER
+        .reap_port(preferred, &target_browser_path)
+        .await;
 
+    /*
+     ** probe the preferred port (is anything already listening?)
+     */
+    if conflict.is_none() {
+        match get_browser_info(&preferred_endpoint).await {
+            Ok(version) => {
+                let running = version.family(Some(&target_browser_path));
+                if running == BrowserFamily::from_id(selected_id) {
+                    /*
+                     ** reuse existing instance on the preferred port
+                     */
+                    let ws_url = get_browser_websocket_url(&preferred_endpoint, 20, 500).await?;
+
+                    /*
+                     ** remember (not launched by us, unless it's already in the registry)
+                     */
+                    let instance_id = adopt_external_instance(
+                        &target_browser_path,
+                        selected_id,
+                        preferred_endpoint.with_ws_url(&ws_url)?,
+                        &ws_url,
+                    )
+                    .await;
+                    let launched = LaunchedInstance {
+                        instance_id,
+                        ws_url: Some(ws_url),
+                        starter_page_error: None,
+                    };
+                    return Ok(open_starter_page(launched, starter_page.as_deref()).await);
+                }
+                conflict = Some(format!("another browser ({})", running.as_str()));
+            }
+            Err(_) if !is_port_free(preferred) => {
+                conflict = Some("another process".to_string());
+            }
+            Err(_) => {
                 /*
-                 ** remember (not launched by us)
+                 ** nothing on that port — proceed to launch a fresh instance on it
                  */
-                {
-                    let mut managed = MANAGED_BROWSER.lock().await;
-                    *managed = Some(ManageableBrowserInstance {
-                        path: target_browser_path.clone(),
-                        port,
-                        ws_url: ws_url.clone(),
-                        child: None,
-                        launched_by_app: false,
-                    });
-                }
-                let _ = create_new_page(port, Some("https://www.google.com")).await;
-
-                return Ok(ws_url);
-            } else {
-                return Err(format!(
-                    "Static port {port} is already occupied by another browser ({running}). Close it and retry."
-                ));
             }
         }
-        Err(_) => {
+    }
+
+    let port = match conflict {
+        None => preferred,
+        Some(owner) if !policy.auto_select => {
+            return Err(RuntimeError::PortOccupied {
+                port: preferred,
+                owner,
+            });
+        }
+        Some(owner) => {
             /*
-             ** nothing on that port — proceed to launch a fresh instance on it
+             ** 0 lets the browser pick when the whole fallback range is taken
              */
+            let port = find_free_port(policy.fallback).unwrap_or(0);
+            warn!("port {preferred} is occupied by {owner}; launching on {port} instead");
+            port
         }
-    }
+    };
 
     /*
-     ** launch on the static port
+     ** launch on the chosen port
      */
-    match launch_new_instance(&target_browser_path, port).await {
-        Ok(ws_url) => {
-            /*
-             ** starter tab (keeps process alive / makes pages() non-empty)
-             */
-            if let Ok(pstr) = extract_port_from_ws_url(&ws_url) {
-                if let Ok(p) = pstr.parse::<u16>() {
-                    let _ = create_new_page(p, Some("https://www.google.com")).await;
-                }
-            }
-            Ok(ws_url)
+    let launched = launch_new_instance(LaunchSpec {
+        path: target_browser_path,
+        transport: LaunchTransport::Port(port),
+        log_to_file,
+        profile,
+        options,
+    })
+    .await?;
+    Ok(open_starter_page(launched, starter_page.as_deref()).await)
+}
+
+/*
+** starter tab (keeps process alive / makes pages() non-empty). the browser
+** is usable without it, so a failure is logged and reported, not returned.
+*/
+async fn open_starter_page(
+    mut launched: LaunchedInstance,
+    starter_page: Option<&str>,
+) -> LaunchedInstance {
+    if let Some(url) = starter_page {
+        if let Err(e) = targets::open_page(Some(&launched.instance_id), url).await {
+            warn!(
+                "failed to open starter page {url} in {}: {e}",
+                launched.instance_id
+            );
+            launched.starter_page_error = Some(e.to_string());
         }
-        Err(e) => Err(format!("Failed to launch browser: {e}")),
     }
+    launched
 }
 
 #[tauri::command]
-pub async fn disconnect_from_browser() -> Result<(), String> {
-    sunset_browser_instance().await
+#[tracing::instrument(skip_all, err)]
+pub async fn list_browser_instances() -> Result<Vec<BrowserInstanceInfo>, RuntimeError> {
+    Ok(BROWSER_MANAGER.list().await)
 }
 
 #[tauri::command]
-pub async fn force_close_browser() -> Result<(), String> {
-    use crate::browser_manager::MANAGED_BROWSER;
-    let mut managed_browser_guard = MANAGED_BROWSER.lock().await;
+#[tracing::instrument(skip_all, fields(instance_id = %instance_id), err)]
+pub async fn select_browser_instance(instance_id: String) -> Result<(), RuntimeError> {
+    BROWSER_MANAGER.select(&instance_id).await
+}
 
-    if let Some(mut instance) = managed_browser_guard.take() {
-        if instance.launched_by_app {
-            if let Some(ref mut child) = instance.child {
-                let _ = child.kill();
-                let _ = child.wait();
-            }
-        } else {
-            /*
-             ** respect external instances – put it back
-             */
-            *managed_browser_guard = Some(instance);
-        }
-        Ok(())
-    } else {
-        Ok(())
+/*
+** close one instance: our own processes are shut down, browsers we only
+** attached to lose their CDP session and registry entry but keep running.
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = %instance_id), err)]
+pub async fn close_browser_instance(instance_id: String) -> Result<(), RuntimeError> {
+    sunset_browser_instance(Some(&instance_id)).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn disconnect_from_browser(instance_id: Option<String>) -> Result<(), RuntimeError> {
+    sunset_browser_instance(instance_id.as_deref()).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn force_close_browser(instance_id: Option<String>) -> Result<(), RuntimeError> {
+    /*
+     ** respect external instances – leave them registered
+     */
+    let removed = BROWSER_MANAGER
+        .take(instance_id.as_deref(), TakeIf::LaunchedByApp)
+        .await?;
+
+    if let Some(TakenInstance { mut instance, .. }) = removed {
+        shutdown_browser(&mut instance, true).await;
     }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn scan_for_existing_browsers(browser_type: String) -> Result<Option<String>, String> {
+#[tracing::instrument(skip_all, err)]
+pub async fn scan_for_existing_browsers(
+    browser_type: String,
+) -> Result<Option<String>, RuntimeError> {
     Ok(scan_for_existing_browser_instances(&browser_type).await)
 }
 
+/*
+** every DevTools endpoint on this machine, not just the first match
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn scan_for_devtools_endpoints(
+    browser_type: Option<String>,
+) -> Result<Vec<DiscoveredEndpoint>, RuntimeError> {
+    Ok(scan_for_endpoints(browser_type.as_deref()).await)
+}
+
+/*
+** attach to a DevTools server elsewhere (a container, a VM, another host),
+** e.g. "http://10.0.0.5:9222" or "wss://host/devtools/browser/<id>".
+** Chrome refuses HTTP requests whose Host is not an IP address or localhost,
+** so hostnames only work behind a proxy that rewrites it.
+*/
 #[tauri::command]
-pub async fn debug_browser_connection(browser_path: String) -> Result<String, String> {
+#[tracing::instrument(skip_all, fields(%url), err)]
+pub async fn add_remote_endpoint(url: String) -> Result<LaunchedInstance, RuntimeError> {
+    let endpoint = CdpEndpoint::parse(&url)?;
+    let version = get_browser_info(&endpoint).await?;
+    let browser_id = version.family(None).as_str();
+    let compatibility = version.compatibility();
+    if !compatibility.supported {
+        warn!(
+            "remote browser may not work as expected: {}",
+            compatibility.issues.join("; ")
+        );
+    }
+
+    let ws_url = get_browser_websocket_url(&endpoint, 1, 0).await?;
+    let endpoint = endpoint.with_ws_url(&ws_url)?;
+    let path = endpoint.http_url("");
+    let instance_id = adopt_external_instance(&path, browser_id, endpoint, &ws_url).await;
+    info!(
+        "attached to remote {} at {ws_url} as {instance_id}",
+        version.product
+    );
+
+    Ok(LaunchedInstance {
+        instance_id,
+        ws_url: Some(ws_url),
+        starter_page_error: None,
+    })
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%browser_path), err)]
+pub async fn debug_browser_connection(browser_path: String) -> Result<String, RuntimeError> {
     let mut debug_info = Vec::new();
 
     if std::path::Path::new(&browser_path).exists() {
@@ -257,11 +451,11 @@ pub async fn debug_browser_connection(browser_path: String) -> Result<String, St
         return Ok("Browser executable not found at specified path".to_string());
     }
 
-    let browsers = detect_browsers();
+    let browsers = available_browsers().await;
     if let Some(target_browser) = browsers.iter().find(|b| b.path == browser_path) {
         debug_info.push(format!("Browser detected as: {}", target_browser.id));
 
-        if let Some(ws_url) = scan_for_existing_browser_instances(&target_browser.id).await {
+        if let Some(ws_url) = scan_for_existing_browser_instances(target_browser.family()).await {
             debug_info.push(format!(
                 "Found existing {} instance: {}",
                 target_browser.id, ws_url
@@ -272,48 +466,440 @@ pub async fn debug_browser_connection(browser_path: String) -> Result<String, St
         }
     }
 
-    if let Some(port) = find_free_port(9222) {
-        debug_info.push(format!("Found free port: {}", port));
-
-        debug_info.push("Attempting to launch browser...".to_string());
+    /*
+     ** port 0: let the OS pick, the browser reports back which one it got
+     */
+    debug_info.push("Attempting to launch browser on an OS-assigned port...".to_string());
 
-        match launch_new_instance(&browser_path, port).await {
-            Ok(ws_url) => {
-                debug_info.push(format!(
-                    "Successfully launched browser with WebSocket: {}",
-                    ws_url
-                ));
+    /*
+     ** throwaway profile, so a browser already open on the default one doesn't interfere
+     */
+    let profile = format!("{}-connection-test", browser_id_for_path(&browser_path));
+    let extra_args = find_browser_by_path(&browser_path)
+        .await
+        .map(|b| b.extra_args)
+        .unwrap_or_default();
+    match launch_new_instance(LaunchSpec {
+        path: browser_path,
+        transport: LaunchTransport::Port(0),
+        log_to_file: true,
+        profile: Some(profile),
+        options: LaunchOptions {
+            extra_args,
+            ..Default::default()
+        },
+    })
+    .await
+    {
+        Ok(launched) => {
+            debug_info.push(format!(
+                "Successfully launched browser with WebSocket: {}",
+                launched.ws_url.unwrap_or_default()
+            ));
 
-                let _ = sunset_browser_instance().await;
-                debug_info.push("🧹 Cleaned up test instance".to_string());
-            }
-            Err(e) => {
-                debug_info.push(format!("Failed to launch browser: {}", e));
+            let _ = sunset_browser_instance(Some(&launched.instance_id)).await;
+            debug_info.push("🧹 Cleaned up test instance".to_string());
+        }
+        Err(e) => {
+            debug_info.push(format!("Failed to launch browser: {}", e));
+
+            let output = get_browser_logs(None, Some(20)).await.unwrap_or_default();
+            if !output.is_empty() {
+                debug_info.push("Browser output:".to_string());
+                debug_info.extend(
+                    output
+                        .iter()
+                        .map(|l| format!("  [{}] {}", l.stream, l.line)),
+                );
             }
         }
-    } else {
-        debug_info.push("No free ports available".to_string());
     }
 
     Ok(debug_info.join("\n"))
 }
 
+/*
+** last `lines` lines of a browser's stdout/stderr. without an instance id this
+** returns the output of the most recent launch attempt, even a failed one.
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn get_browser_logs(
+    instance_id: Option<String>,
+    lines: Option<usize>,
+) -> Result<Vec<BrowserLogLine>, RuntimeError> {
+    let lines = lines.unwrap_or(200);
+
+    let logs = match instance_id {
+        Some(instance_id) => BROWSER_MANAGER.snapshot(Some(&instance_id)).await?.logs,
+        None => LAST_LAUNCH_LOGS.lock().unwrap().clone(),
+    };
+
+    Ok(logs.map(|logs| logs.tail(lines)).unwrap_or_default())
+}
+
+/*
+** the app's own log records, newest `limit` matching ones, for bug reports
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_app_logs(query: Option<LogQuery>) -> Result<Vec<LogRecord>, RuntimeError> {
+    Ok(logging::query_logs(&query.unwrap_or_default()))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_log_level() -> Result<LogLevel, RuntimeError> {
+    Ok(logging::log_level())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn set_log_level(level: LogLevel) -> Result<(), RuntimeError> {
+    logging::set_log_level(level)
+}
+
+/*
+** raw CDP passthrough on a managed browser. `session_id` targets a
+** flattened session obtained from `attach_to_target`.
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %method), err)]
+pub async fn send_cdp_command(
+    method: String,
+    params: Option<serde_json::Value>,
+    session_id: Option<String>,
+    instance_id: Option<String>,
+) -> Result<serde_json::Value, RuntimeError> {
+    let session = managed_cdp_session(instance_id.as_deref()).await?;
+    let params = params.unwrap_or_else(|| serde_json::json!({}));
+    match session_id {
+        Some(session_id) => session.send_to(&session_id, &method, params).await,
+        None => session.send(&method, params).await,
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn attach_to_target(
+    target_id: String,
+    instance_id: Option<String>,
+) -> Result<String, RuntimeError> {
+    let session = managed_cdp_session(instance_id.as_deref()).await?;
+    let target = session.attach_to_target(&target_id).await?;
+    Ok(target.session_id)
+}
+
+/*
+** product, engine versions and whether we support them, for the selected
+** instance by default
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn get_browser_version(
+    instance_id: Option<String>,
+) -> Result<BrowserVersionReport, RuntimeError> {
+    let instance = BROWSER_MANAGER.snapshot(instance_id.as_deref()).await?;
+    /*
+     ** a remote instance's path is its URL, which says nothing about the browser
+     */
+    let local = instance.endpoint.as_ref().is_none_or(|e| e.is_local());
+    let hint = local.then_some(instance.path);
+
+    let version = match instance.endpoint {
+        Some(endpoint) => get_browser_info(&endpoint).await?,
+        None => {
+            let session = managed_cdp_session(instance_id.as_deref()).await?;
+            let result = session
+                .send("Browser.getVersion", serde_json::json!({}))
+                .await?;
+            BrowserVersion::from_cdp(&result).map_err(RuntimeError::cdp)?
+        }
+    };
+    Ok(version.report(hint.as_deref()))
+}
+
+/*
+** tabs, workers and other targets of an instance (the selected one by default)
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn list_targets(instance_id: Option<String>) -> Result<Vec<BrowserTarget>, RuntimeError> {
+    targets::list_targets(instance_id.as_deref()).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn activate_target(
+    target_id: String,
+    instance_id: Option<String>,
+) -> Result<(), RuntimeError> {
+    targets::activate_target(instance_id.as_deref(), &target_id).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn close_target(
+    target_id: String,
+    instance_id: Option<String>,
+) -> Result<(), RuntimeError> {
+    targets::close_target(instance_id.as_deref(), &target_id).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn reload_target(
+    target_id: String,
+    ignore_cache: Option<bool>,
+    instance_id: Option<String>,
+) -> Result<(), RuntimeError> {
+    targets::reload_target(
+        instance_id.as_deref(),
+        &target_id,
+        ignore_cache.unwrap_or(false),
+    )
+    .await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %url), err)]
+pub async fn open_target(url: String, instance_id: Option<String>) -> Result<String, RuntimeError> {
+    targets::open_page(instance_id.as_deref(), &url).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn capture_screenshot(
+    target_id: String,
+    options: Option<ScreenshotOptions>,
+    instance_id: Option<String>,
+) -> Result<Screenshot, RuntimeError> {
+    screenshots::capture_screenshot(
+        instance_id.as_deref(),
+        &target_id,
+        &options.unwrap_or_default(),
+    )
+    .await
+}
+
+/*
+** saved under the session's artifacts folder; see list_session_artifacts
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id, %session_id), err)]
+pub async fn print_to_pdf(
+    target_id: String,
+    session_id: String,
+    options: Option<PdfOptions>,
+    instance_id: Option<String>,
+) -> Result<Artifact, RuntimeError> {
+    printing::print_to_pdf(
+        instance_id.as_deref(),
+        &target_id,
+        &session_id,
+        &options.unwrap_or_default(),
+    )
+    .await
+}
+
+/*
+** send the instance's downloads to `folder`, or to the app's own folder for
+** it; returns the folder. browsers launched by the app get this on launch.
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn enable_downloads(
+    instance_id: Option<String>,
+    folder: Option<String>,
+) -> Result<String, RuntimeError> {
+    downloads::enable_downloads(instance_id.as_deref(), folder.as_deref())
+        .await
+        .map(|folder| folder.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn list_downloads(
+    instance_id: Option<String>,
+    include_in_progress: Option<bool>,
+) -> Result<Vec<Download>, RuntimeError> {
+    downloads::list_downloads(instance_id.as_deref(), include_in_progress.unwrap_or(false)).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref()), err)]
+pub async fn wait_for_download(
+    instance_id: Option<String>,
+    timeout_ms: Option<u64>,
+    since_ms: Option<u64>,
+) -> Result<Download, RuntimeError> {
+    downloads::wait_for_download(instance_id.as_deref(), timeout_ms, since_ms).await
+}
+
+/*
+** main content of the target as Markdown, for summarizing or answering
+** questions about the current page; `max_tokens` defaults to 4000
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(instance_id = instance_id.as_deref(), %target_id), err)]
+pub async fn extract_page_markdown(
+    target_id: String,
+    max_tokens: Option<usize>,
+    instance_id: Option<String>,
+) -> Result<PageMarkdown, RuntimeError> {
+    readability::page_markdown(instance_id.as_deref(), &target_id, max_tokens).await
+}
+
+/*
+** text of a PDF, DOCX, HTML or text file on disk, page by page; meant for
+** downloads, artifacts and files dropped on the window
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%path), err)]
+pub async fn extract_document_text(
+    path: String,
+    max_chars: Option<usize>,
+) -> Result<ExtractedDocument, RuntimeError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        documents::extract_document(Path::new(&path), max_chars)
+    })
+    .await
+    .map_err(RuntimeError::internal)?
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%session_id), err)]
+pub async fn list_session_artifacts(session_id: String) -> Result<Vec<Artifact>, RuntimeError> {
+    artifacts::list_artifacts(&session_id)
+}
+
+/*
+** `reveal` shows the file in the file manager instead of opening it
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%session_id, %artifact_id), err)]
+pub async fn open_artifact(
+    session_id: String,
+    artifact_id: String,
+    reveal: Option<bool>,
+) -> Result<(), RuntimeError> {
+    artifacts::open_artifact(&session_id, &artifact_id, reveal.unwrap_or(false))
+}
+
+async fn ensure_profile_not_in_use(name: &str) -> Result<(), RuntimeError> {
+    BROWSER_MANAGER.check_profile(name).await
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn list_browser_profiles() -> Result<Vec<BrowserProfileInfo>, RuntimeError> {
+    let in_use = BROWSER_MANAGER.profiles_in_use().await;
+    profiles::list_profiles(|name| in_use.contains(name))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%name), err)]
+pub async fn create_browser_profile(
+    name: String,
+    browser_id: String,
+) -> Result<BrowserProfileInfo, RuntimeError> {
+    profiles::create_profile(&name, &browser_id)
+}
+
+/*
+** copies the user-data dir too, so logins carry over to the clone
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn clone_browser_profile(
+    source: String,
+    target: String,
+) -> Result<BrowserProfileInfo, RuntimeError> {
+    ensure_profile_not_in_use(&source).await?;
+    profiles::clone_profile(&source, &target)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%name), err)]
+pub async fn rename_browser_profile(
+    name: String,
+    new_name: String,
+) -> Result<BrowserProfileInfo, RuntimeError> {
+    ensure_profile_not_in_use(&name).await?;
+    profiles::rename_profile(&name, &new_name)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(%name), err)]
+pub async fn delete_browser_profile(name: String) -> Result<(), RuntimeError> {
+    ensure_profile_not_in_use(&name).await?;
+    profiles::delete_profile(&name)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_port_policy() -> Result<PortPolicy, RuntimeError> {
+    Ok(port_policy())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn set_port_policy(policy: PortPolicy) -> Result<(), RuntimeError> {
+    save_port_policy(&policy)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_http_settings() -> Result<HttpSettings, RuntimeError> {
+    Ok(http_settings())
+}
+
+/*
+** timeouts, retries and proxy for the shared HTTP clients; applies to the next request
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn set_http_settings(settings: HttpSettings) -> Result<(), RuntimeError> {
+    save_http_settings(&settings)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn get_restart_policy() -> Result<RestartPolicy, RuntimeError> {
+    Ok(RESTART_POLICY.read().await.clone())
+}
+
+/*
+** applies to every supervised instance from its next crash on
+*/
+#[tauri::command]
+#[tracing::instrument(skip_all, err)]
+pub async fn set_restart_policy(policy: RestartPolicy) -> Result<(), RuntimeError> {
+    if policy.probe_interval_ms == 0 {
+        return Err(RuntimeError::InvalidInput {
+            reason: "probe_interval_ms must be greater than 0".to_string(),
+        });
+    }
+    *RESTART_POLICY.write().await = policy;
+    Ok(())
+}
+
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(%domain), err)]
 pub async fn load_skills(
     domain: &str,
     company: Option<String>,
     repo: Option<String>,
     branch: String,
-) -> Result<WebsiteSkills, String> {
-    println!("loading skills for domain: {}", domain);
+) -> Result<WebsiteSkills, RuntimeError> {
+    info!("loading skills for domain: {}", domain);
     download_skill_json(domain.to_string(), company, repo, branch).await
 }
 
 #[tauri::command]
-pub async fn call_app(func: String, args: Vec<String>) -> Result<String, String> {
-    // run the dispatcher ; map Ok() to () and Err() to String
+#[tracing::instrument(skip_all, fields(%func), err)]
+pub async fn call_app(func: String, args: Vec<String>) -> Result<String, RuntimeError> {
+    // run the dispatcher ; map Ok() to "OK"
     let string_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-    call(&func, &string_refs)
-        .map(|_| "OK".to_string())
-        .map_err(|e| e.to_string())
+    call(&func, &string_refs).map(|_| "OK".to_string())
 }
@@ -0,0 +1,144 @@
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+use crate::browser_manager::probe_cdp_support;
+use crate::error::RuntimeError;
+use crate::platform::detect_browsers;
+use crate::settings::{load_setting, save_setting};
+use crate::sketchs::BrowserConfig;
+use tracing::warn;
+
+const CUSTOM_BROWSERS_KEY: &str = "custom_browsers";
+
+/*
+** detection runs `--version` on every candidate, so it happens once and
+** then only again on an explicit rescan
+*/
+static DETECTED: Lazy<RwLock<Option<Vec<BrowserConfig>>>> = Lazy::new(|| RwLock::new(None));
+
+pub fn custom_browsers() -> Vec<BrowserConfig> {
+    load_setting(CUSTOM_BROWSERS_KEY).unwrap_or_default()
+}
+
+fn save_custom_browsers(browsers: &[BrowserConfig]) -> Result<(), RuntimeError> {
+    save_setting(CUSTOM_BROWSERS_KEY, &browsers)
+}
+
+/*
+** scan the machine again and remember the result
+*/
+pub async fn rescan_browsers() -> Vec<BrowserConfig> {
+    let detected = tokio::task::spawn_blocking(detect_browsers)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("browser detection failed: {e}");
+            Vec::new()
+        });
+    *DETECTED.write().unwrap() = Some(detected.clone());
+    detected
+}
+
+async fn detected_browsers() -> Vec<BrowserConfig> {
+    let cached = DETECTED.read().unwrap().clone();
+    match cached {
+        Some(detected) => detected,
+        None => rescan_browsers().await,
+    }
+}
+
+/*
+** detected browsers plus the user's own entries. a custom entry wins over a
+** detected one with the same path, so its name and extra args apply.
+*/
+pub async fn available_browsers() -> Vec<BrowserConfig> {
+    let custom = custom_browsers();
+    let mut browsers: Vec<BrowserConfig> = detected_browsers()
+        .await
+        .into_iter()
+        .filter(|b| !custom.iter().any(|c| c.path == b.path))
+        .collect();
+    browsers.extend(custom);
+    browsers
+}
+
+pub async fn find_browser_by_path(path: &str) -> Option<BrowserConfig> {
+    available_browsers()
+        .await
+        .into_iter()
+        .find(|b| b.path == path)
+}
+
+fn validate_entry(browser: &BrowserConfig) -> Result<(), RuntimeError> {
+    let invalid = |reason: String| Err(RuntimeError::InvalidInput { reason });
+    if browser.id.is_empty()
+        || !browser
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return invalid(format!(
+            "Invalid browser id '{}': use letters, digits, '-' or '_'",
+            browser.id
+        ));
+    }
+    if browser.name.trim().is_empty() {
+        return invalid("Browser name must not be empty".to_string());
+    }
+    if !std::path::Path::new(&browser.path).is_file() {
+        return invalid(format!("No browser executable at {}", browser.path));
+    }
+    Ok(())
+}
+
+/*
+** check the entry, make sure the binary actually speaks CDP and fill in its version
+*/
+async fn prepare_entry(mut browser: BrowserConfig) -> Result<BrowserConfig, RuntimeError> {
+    validate_entry(&browser)?;
+    let version = probe_cdp_support(&browser.path, &browser.extra_args).await?;
+    browser.version = Some(version);
+    browser.custom = true;
+    Ok(browser)
+}
+
+pub async fn add_custom_browser(browser: BrowserConfig) -> Result<BrowserConfig, RuntimeError> {
+    let mut browsers = custom_browsers();
+    if browsers.iter().any(|b| b.id == browser.id) {
+        return Err(RuntimeError::CustomBrowserExists { id: browser.id });
+    }
+
+    let browser = prepare_entry(browser).await?;
+    browsers.push(browser.clone());
+    save_custom_browsers(&browsers)?;
+    Ok(browser)
+}
+
+pub async fn update_custom_browser(
+    id: &str,
+    browser: BrowserConfig,
+) -> Result<BrowserConfig, RuntimeError> {
+    let mut browsers = custom_browsers();
+    let index = browsers
+        .iter()
+        .position(|b| b.id == id)
+        .ok_or_else(|| RuntimeError::CustomBrowserNotFound { id: id.to_string() })?;
+    if browser.id != id && browsers.iter().any(|b| b.id == browser.id) {
+        return Err(RuntimeError::CustomBrowserExists { id: browser.id });
+    }
+
+    let browser = prepare_entry(browser).await?;
+    browsers[index] = browser.clone();
+    save_custom_browsers(&browsers)?;
+    Ok(browser)
+}
+
+pub fn remove_custom_browser(id: &str) -> Result<(), RuntimeError> {
+    let mut browsers = custom_browsers();
+    let before = browsers.len();
+    browsers.retain(|b| b.id != id);
+    if browsers.len() == before {
+        return Err(RuntimeError::CustomBrowserNotFound { id: id.to_string() });
+    }
+    save_custom_browsers(&browsers)
+}
@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::sketchs::BrowserConfig;
+use tracing::debug;
+
+const VERSION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/*
+** chromium-based browsers we know how to drive, in the order they are offered.
+** (browser id, display name, channel, executable names)
+*/
+const KNOWN_BROWSERS: &[(&str, &str, &str, &[&str])] = &[
+    (
+        "chrome",
+        "Google Chrome",
+        "stable",
+        &["google-chrome-stable", "google-chrome"],
+    ),
+    (
+        "chrome",
+        "Google Chrome Beta",
+        "beta",
+        &["google-chrome-beta"],
+    ),
+    (
+        "chrome",
+        "Google Chrome Dev",
+        "dev",
+        &["google-chrome-unstable"],
+    ),
+    (
+        "chrome",
+        "Google Chrome Canary",
+        "canary",
+        &["google-chrome-canary"],
+    ),
+    (
+        "chromium",
+        "Chromium",
+        "stable",
+        &["chromium", "chromium-browser"],
+    ),
+    (
+        "edge",
+        "Microsoft Edge",
+        "stable",
+        &["microsoft-edge-stable", "microsoft-edge"],
+    ),
+    (
+        "edge",
+        "Microsoft Edge Beta",
+        "beta",
+        &["microsoft-edge-beta"],
+    ),
+    ("edge", "Microsoft Edge Dev", "dev", &["microsoft-edge-dev"]),
+    (
+        "brave",
+        "Brave",
+        "stable",
+        &["brave-browser-stable", "brave-browser", "brave"],
+    ),
+    ("brave", "Brave Beta", "beta", &["brave-browser-beta"]),
+    (
+        "brave",
+        "Brave Nightly",
+        "nightly",
+        &["brave-browser-nightly"],
+    ),
+    (
+        "vivaldi",
+        "Vivaldi",
+        "stable",
+        &["vivaldi-stable", "vivaldi"],
+    ),
+    (
+        "vivaldi",
+        "Vivaldi Snapshot",
+        "snapshot",
+        &["vivaldi-snapshot"],
+    ),
+    ("opera", "Opera", "stable", &["opera"]),
+    ("opera", "Opera Beta", "beta", &["opera-beta"]),
+    (
+        "opera",
+        "Opera Developer",
+        "developer",
+        &["opera-developer"],
+    ),
+    ("arc", "Arc", "stable", &["arc"]),
+];
+
+/*
+** flatpak app ids and the KNOWN_BROWSERS entry (by display name) they map to
+*/
+const FLATPAK_APPS: &[(&str, &str)] = &[
+    ("com.google.Chrome", "Google Chrome"),
+    ("com.google.ChromeDev", "Google Chrome Dev"),
+    ("org.chromium.Chromium", "Chromium"),
+    (
+        "io.github.ungoogled_software.ungoogled_chromium",
+        "Chromium",
+    ),
+    ("com.microsoft.Edge", "Microsoft Edge"),
+    ("com.brave.Browser", "Brave"),
+    ("com.vivaldi.Vivaldi", "Vivaldi"),
+    ("com.opera.Opera", "Opera"),
+];
+
+/*
+** install locations that are usually not on $PATH
+*/
+const EXTRA_DIRS: &[&str] = &[
+    "/usr/bin",
+    "/usr/local/bin",
+    "/snap/bin",
+    "/opt/google/chrome",
+    "/opt/google/chrome-beta",
+    "/opt/google/chrome-unstable",
+    "/opt/microsoft/msedge",
+    "/opt/microsoft/msedge-beta",
+    "/opt/microsoft/msedge-dev",
+    "/opt/brave.com/brave",
+    "/opt/vivaldi",
+    "/opt/arc",
+];
+
+struct Candidate {
+    path: PathBuf,
+    known: usize,
+    source: Source,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Native,
+    Snap,
+    Flatpak,
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+fn known_by_executable(file_name: &str) -> Option<usize> {
+    KNOWN_BROWSERS
+        .iter()
+        .position(|(_, _, _, names)| names.contains(&file_name))
+}
+
+fn known_by_name(name: &str) -> Option<usize> {
+    KNOWN_BROWSERS.iter().position(|(_, n, _, _)| *n == name)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+    dirs.extend(EXTRA_DIRS.iter().map(PathBuf::from));
+    dirs
+}
+
+fn flatpak_export_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/var/lib/flatpak/exports/bin")];
+    if let Some(home) = home_dir() {
+        dirs.push(home.join(".local/share/flatpak/exports/bin"));
+    }
+    dirs
+}
+
+fn source_of(path: &Path) -> Source {
+    if path.starts_with("/snap/") {
+        Source::Snap
+    } else {
+        Source::Native
+    }
+}
+
+fn scan_search_dirs(candidates: &mut Vec<Candidate>) {
+    for dir in search_dirs() {
+        for (known, (_, _, _, names)) in KNOWN_BROWSERS.iter().enumerate() {
+            for name in names.iter() {
+                let path = dir.join(name);
+                if is_executable(&path) {
+                    candidates.push(Candidate {
+                        source: source_of(&path),
+                        path,
+                        known,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn scan_flatpak(candidates: &mut Vec<Candidate>) {
+    for dir in flatpak_export_dirs() {
+        for (app_id, name) in FLATPAK_APPS {
+            let path = dir.join(app_id);
+            if let (true, Some(known)) = (is_executable(&path), known_by_name(name)) {
+                candidates.push(Candidate {
+                    path,
+                    known,
+                    source: Source::Flatpak,
+                });
+            }
+        }
+    }
+}
+
+/*
+** "applications" under $XDG_DATA_HOME and under every $XDG_DATA_DIRS entry
+*/
+fn desktop_dirs() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => roots.push(PathBuf::from(dir)),
+        None => roots.extend(home_dir().map(|home| home.join(".local/share"))),
+    }
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    roots.extend(data_dirs.split(':').map(PathBuf::from));
+    roots
+        .into_iter()
+        .map(|root| root.join("applications"))
+        .collect()
+}
+
+/*
+** first Exec= line of the [Desktop Entry] group, split into words.
+** quoting follows the desktop entry spec closely enough for browser launchers.
+*/
+fn desktop_exec(contents: &str) -> Option<Vec<String>> {
+    let mut in_entry = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        if let Some(exec) = line.strip_prefix("Exec=") {
+            let mut words = Vec::new();
+            let mut word = String::new();
+            let mut quoted = false;
+            for c in exec.chars() {
+                match c {
+                    '"' => quoted = !quoted,
+                    c if c.is_whitespace() && !quoted => {
+                        if !word.is_empty() {
+                            words.push(std::mem::take(&mut word));
+                        }
+                    }
+                    c => word.push(c),
+                }
+            }
+            if !word.is_empty() {
+                words.push(word);
+            }
+            return Some(words);
+        }
+    }
+    None
+}
+
+/*
+** what an Exec line starts once `env VAR=...` and `flatpak run` wrappers
+** are looked through
+*/
+#[derive(Debug, PartialEq, Eq)]
+enum ExecProgram<'a> {
+    Binary(&'a str),
+    Flatpak(&'a str),
+}
+
+fn exec_program(words: &[String]) -> Option<ExecProgram<'_>> {
+    let mut words = words.iter().map(String::as_str).peekable();
+    if words
+        .peek()
+        .is_some_and(|w| *w == "env" || w.ends_with("/env"))
+    {
+        words.next();
+        while words.peek().is_some_and(|w| w.contains('=')) {
+            words.next();
+        }
+    }
+
+    let program = words.next()?;
+    if program == "flatpak" || program.ends_with("/flatpak") {
+        let app_id = words.find(|w| !w.starts_with('-') && *w != "run")?;
+        return Some(ExecProgram::Flatpak(app_id));
+    }
+    Some(ExecProgram::Binary(program))
+}
+
+/*
+** the browser binary an Exec line starts
+*/
+fn exec_target(words: &[String]) -> Option<(PathBuf, Source)> {
+    match exec_program(words)? {
+        ExecProgram::Flatpak(app_id) => flatpak_export_dirs()
+            .into_iter()
+            .map(|dir| dir.join(app_id))
+            .find(|path| is_executable(path))
+            .map(|path| (path, Source::Flatpak)),
+        ExecProgram::Binary(program) => {
+            let path = if program.contains('/') {
+                PathBuf::from(program)
+            } else {
+                search_dirs()
+                    .into_iter()
+                    .map(|dir| dir.join(program))
+                    .find(|path| is_executable(path))?
+            };
+            let source = source_of(&path);
+            Some((path, source))
+        }
+    }
+}
+
+fn scan_desktop_entries(candidates: &mut Vec<Candidate>) {
+    for dir in desktop_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
+                continue;
+            }
+            let Some((target, source)) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|contents| desktop_exec(&contents))
+                .and_then(|words| exec_target(&words))
+            else {
+                continue;
+            };
+
+            let known = match source {
+                Source::Flatpak => target
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|app_id| FLATPAK_APPS.iter().find(|(id, _)| *id == app_id))
+                    .and_then(|(_, name)| known_by_name(name)),
+                _ => target
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(known_by_executable),
+            };
+            if let (Some(known), true) = (known, is_executable(&target)) {
+                candidates.push(Candidate {
+                    path: target,
+                    known,
+                    source,
+                });
+            }
+        }
+    }
+}
+
+/*
+** "Google Chrome 126.0.6478.126" / "Chromium 126.0.6478.126 snap" -> "126.0.6478.126"
+*/
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .find(|word| {
+            word.contains('.')
+                && word.starts_with(|c: char| c.is_ascii_digit())
+                && word.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+        .map(str::to_string)
+}
+
+/*
+** `<browser> --version`, killed if it hangs (some wrappers try to start a UI)
+*/
+fn read_version(path: &Path) -> Option<String> {
+    let mut child = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() < VERSION_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/*
+** /usr/bin/google-chrome, /usr/bin/google-chrome-stable and
+** /opt/google/chrome/google-chrome are all the same browser. every
+** /snap/bin/<app> links to /usr/bin/snap though, so snaps keep their own path.
+*/
+fn dedupe(candidates: &mut Vec<Candidate>) {
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| {
+        let key = match candidate.source {
+            Source::Snap => candidate.path.clone(),
+            Source::Native | Source::Flatpak => {
+                fs::canonicalize(&candidate.path).unwrap_or_else(|_| candidate.path.clone())
+            }
+        };
+        seen.insert(key)
+    });
+}
+
+pub fn discover_browsers() -> Vec<BrowserConfig> {
+    let mut candidates = Vec::new();
+    scan_search_dirs(&mut candidates);
+    scan_flatpak(&mut candidates);
+    scan_desktop_entries(&mut candidates);
+
+    dedupe(&mut candidates);
+    candidates.sort_by_key(|candidate| candidate.known);
+
+    let versions: Vec<Option<String>> = std::thread::scope(|scope| {
+        let probes: Vec<_> = candidates
+            .iter()
+            .map(|candidate| scope.spawn(|| read_version(&candidate.path)))
+            .collect();
+        probes
+            .into_iter()
+            .map(|probe| probe.join().ok().flatten())
+            .collect()
+    });
+
+    candidates
+        .into_iter()
+        .zip(versions)
+        .map(|(candidate, version)| {
+            let (id, name, channel, _) = KNOWN_BROWSERS[candidate.known];
+            let name = match candidate.source {
+                Source::Snap => format!("{name} (Snap)"),
+                Source::Flatpak => format!("{name} (Flatpak)"),
+                Source::Native => name.to_string(),
+            };
+            let path = candidate.path.to_string_lossy().to_string();
+            debug!("Found browser: {name} at {path}");
+            BrowserConfig {
+                id: id.to_string(),
+                name,
+                path,
+                channel: Some(channel.to_string()),
+                version,
+                extra_args: Vec::new(),
+                custom: false,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn exec_lines_resolve_to_the_browser() {
+        let cases = [
+            (
+                "/usr/bin/google-chrome-stable %U",
+                Some(ExecProgram::Binary("/usr/bin/google-chrome-stable")),
+            ),
+            ("brave-browser --incognito", Some(ExecProgram::Binary("brave-browser"))),
+            (
+                "env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/chromium_chromium.desktop /snap/bin/chromium %U",
+                Some(ExecProgram::Binary("/snap/bin/chromium")),
+            ),
+            (
+                "/usr/bin/env GDK_BACKEND=x11 MOZ=1 microsoft-edge %U",
+                Some(ExecProgram::Binary("microsoft-edge")),
+            ),
+            (
+                "\"/opt/Vivaldi Browser/vivaldi\" --new-window",
+                Some(ExecProgram::Binary("/opt/Vivaldi Browser/vivaldi")),
+            ),
+            (
+                "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=brave --file-forwarding com.brave.Browser @@u %U @@",
+                Some(ExecProgram::Flatpak("com.brave.Browser")),
+            ),
+            ("flatpak run org.chromium.Chromium", Some(ExecProgram::Flatpak("org.chromium.Chromium"))),
+            ("env FOO=1", None),
+            ("flatpak run --command=sh", None),
+        ];
+        for (exec, expected) in cases {
+            let entry = format!("[Desktop Entry]\nName=Browser\nExec={exec}\n");
+            let words = desktop_exec(&entry).unwrap();
+            assert_eq!(exec_program(&words), expected, "{exec}");
+        }
+    }
+
+    #[test]
+    fn only_the_desktop_entry_group_counts() {
+        let entry = "[Desktop Action new-window]\n\
+                     Exec=/usr/bin/opera --new-window\n\
+                     \n\
+                     [Desktop Entry]\n\
+                     Name=Opera\n\
+                     Exec=/usr/bin/opera %U\n\
+                     Exec=/usr/bin/ignored\n";
+        assert_eq!(
+            desktop_exec(entry),
+            Some(vec!["/usr/bin/opera".to_string(), "%U".to_string()])
+        );
+        assert_eq!(desktop_exec("[Desktop Entry]\nName=No exec\n"), None);
+        assert_eq!(desktop_exec("Exec=/usr/bin/chromium\n"), None);
+    }
+
+    #[test]
+    fn versions_come_from_the_first_dotted_number() {
+        let cases = [
+            ("Google Chrome 126.0.6478.126 \n", Some("126.0.6478.126")),
+            ("Chromium 126.0.6478.126 snap\n", Some("126.0.6478.126")),
+            ("Microsoft Edge 126.0.2592.87 beta", Some("126.0.2592.87")),
+            ("Brave Browser 126.1.67.116", Some("126.1.67.116")),
+            ("Vivaldi 6.8.3381.46 stable", Some("6.8.3381.46")),
+            ("111.0.5168.61", Some("111.0.5168.61")),
+            ("Chromium 126", None),
+            ("Opera v1.x", None),
+            ("", None),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(parse_version(output).as_deref(), expected, "{output:?}");
+        }
+    }
+
+    #[test]
+    fn snaps_sharing_the_snap_binary_stay_apart() {
+        let dir = std::env::temp_dir().join(format!("runtime-discovery-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("snap"), "").unwrap();
+        fs::write(dir.join("chrome"), "").unwrap();
+        for (link, target) in [
+            ("chromium", "snap"),
+            ("brave", "snap"),
+            ("google-chrome", "chrome"),
+            ("google-chrome-stable", "chrome"),
+        ] {
+            symlink(dir.join(target), dir.join(link)).unwrap();
+        }
+
+        let candidate = |name: &str, source| Candidate {
+            path: dir.join(name),
+            known: 0,
+            source,
+        };
+        let mut candidates = vec![
+            candidate("chromium", Source::Snap),
+            candidate("brave", Source::Snap),
+            candidate("chromium", Source::Snap),
+            candidate("google-chrome", Source::Native),
+            candidate("google-chrome-stable", Source::Native),
+        ];
+        dedupe(&mut candidates);
+        let kept: Vec<_> = candidates
+            .iter()
+            .map(|c| c.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(kept, ["chromium", "brave", "google-chrome"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
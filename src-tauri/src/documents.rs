@@ -0,0 +1,578 @@
+use std::io::{Cursor, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use pdf_extract::{decode_text_string, output_doc_page, Document, Object, PlainTextOutput};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use scraper::{ElementRef, Html, Node};
+use serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::error::RuntimeError;
+
+const MAX_DOCUMENT_BYTES: u64 = 100 * 1024 * 1024;
+const SNIFF_BYTES: usize = 8192;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Pdf,
+    Docx,
+    Html,
+    Text,
+}
+
+impl DocumentKind {
+    fn mime_type(&self) -> &'static str {
+        match self {
+            DocumentKind::Pdf => "application/pdf",
+            DocumentKind::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            DocumentKind::Html => "text/html",
+            DocumentKind::Text => "text/plain",
+        }
+    }
+}
+
+/*
+** `number` starts at 1. a page without text (a scan, a blank page) is kept
+** so numbers match the original.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentPage {
+    pub number: u32,
+    pub text: String,
+}
+
+/*
+** counts describe the whole document, even when `pages` was cut short
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub created: Option<String>,
+    pub page_count: usize,
+    pub word_count: usize,
+}
+
+/*
+** `text` is every page joined by a blank line, ready for an LLM prompt
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtractedDocument {
+    pub path: String,
+    pub kind: DocumentKind,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    pub metadata: DocumentMetadata,
+    pub pages: Vec<DocumentPage>,
+    pub text: String,
+    pub truncated: bool,
+}
+
+/*
+** by content first: downloads often come with a generic or missing extension
+*/
+fn detect_kind(path: &Path, bytes: &[u8]) -> Option<DocumentKind> {
+    let head = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if head.windows(5).take(1024).any(|window| window == b"%PDF-") {
+        return Some(DocumentKind::Pdf);
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        let is_docx = ZipArchive::new(Cursor::new(bytes))
+            .is_ok_and(|archive| archive.index_for_name("word/document.xml").is_some());
+        return is_docx.then_some(DocumentKind::Docx);
+    }
+    if head.contains(&0) {
+        return None;
+    }
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let start = String::from_utf8_lossy(head)
+        .trim_start()
+        .to_ascii_lowercase();
+    if matches!(extension.as_deref(), Some("html" | "htm" | "xhtml"))
+        || start.starts_with("<!doctype html")
+        || start.contains("<html")
+    {
+        return Some(DocumentKind::Html);
+    }
+    Some(DocumentKind::Text)
+}
+
+/*
+** one line per line, no trailing or repeated spaces, at most one blank
+** line in a row. tabs survive: they separate table cells.
+*/
+fn clean_text(raw: &str) -> String {
+    let normalized = raw.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<String> = Vec::new();
+    for line in normalized.split('\n') {
+        let mut cleaned = String::with_capacity(line.len());
+        for c in line.chars() {
+            let c = match c {
+                '\u{00a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{3000}' => ' ',
+                '\u{00ad}' | '\u{200b}'..='\u{200d}' | '\u{feff}' => continue,
+                '\t' => '\t',
+                c if c.is_control() => continue,
+                c => c,
+            };
+            if c == ' ' && (cleaned.ends_with(' ') || cleaned.ends_with('\t')) {
+                continue;
+            }
+            if c == '\t' && cleaned.ends_with(' ') {
+                cleaned.pop();
+            }
+            cleaned.push(c);
+        }
+        let cleaned = cleaned.trim().to_string();
+        if cleaned.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(cleaned);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/*
+** "D:20240315103000+01'00'" -> "2024-03-15T10:30:00+01:00"
+*/
+fn pdf_date(raw: &str) -> String {
+    let value = raw.trim().trim_start_matches("D:");
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return raw.trim().to_string();
+    }
+    let part = |from: usize, default: &'static str| digits.get(from..from + 2).unwrap_or(default);
+    let mut date = format!("{}-{}-{}", &digits[..4], part(4, "01"), part(6, "01"));
+    if digits.len() >= 12 {
+        date.push_str(&format!(
+            "T{}:{}:{}",
+            part(8, "00"),
+            part(10, "00"),
+            part(12, "00")
+        ));
+        let zone = value[digits.len()..].replace('\'', "");
+        match zone.as_str() {
+            "Z" => date.push('Z'),
+            zone if zone.len() == 5 && (zone.starts_with('+') || zone.starts_with('-')) => {
+                date.push_str(&format!("{}:{}", &zone[..3], &zone[3..]))
+            }
+            _ => {}
+        }
+    }
+    date
+}
+
+fn pdf_metadata(doc: &Document) -> DocumentMetadata {
+    let info = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).ok(),
+        Ok(Object::Dictionary(info)) => Some(info),
+        _ => None,
+    };
+    let field = |key: &[u8]| {
+        info.and_then(|info| info.get(key).ok())
+            .and_then(|value| decode_text_string(value).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    DocumentMetadata {
+        title: field(b"Title"),
+        author: field(b"Author"),
+        subject: field(b"Subject"),
+        created: field(b"CreationDate").map(|date| pdf_date(&date)),
+        ..Default::default()
+    }
+}
+
+fn pdf_pages(bytes: &[u8]) -> Result<(Vec<String>, DocumentMetadata), String> {
+    let mut doc = Document::load_mem(bytes).map_err(|e| e.to_string())?;
+    if doc.is_encrypted() {
+        doc.decrypt("")
+            .map_err(|_| "the PDF is password protected".to_string())?;
+    }
+    let metadata = pdf_metadata(&doc);
+    let mut pages = Vec::new();
+    for number in doc.get_pages().into_keys() {
+        let mut text = String::new();
+        output_doc_page(&doc, &mut PlainTextOutput::new(&mut text), number)
+            .map_err(|e| format!("page {number}: {e}"))?;
+        pages.push(text);
+    }
+    Ok((pages, metadata))
+}
+
+fn zip_entry(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<Option<String>, String> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("{name}: {e}")),
+    };
+    let mut xml = String::new();
+    entry
+        .read_to_string(&mut xml)
+        .map_err(|e| format!("{name}: {e}"))?;
+    Ok(Some(xml))
+}
+
+fn is_page_break(element: &BytesStart) -> bool {
+    match element.name().as_ref() {
+        b"w:lastRenderedPageBreak" => true,
+        b"w:br" => element
+            .try_get_attribute("w:type")
+            .ok()
+            .flatten()
+            .is_some_and(|kind| kind.value.as_ref() == b"page"),
+        _ => false,
+    }
+}
+
+/*
+** word/document.xml. pages end at explicit page breaks and at the breaks
+** Word recorded the last time it laid the document out; both tend to show
+** up around the same spot, so a break on an empty page is ignored.
+*/
+fn docx_text(xml: &str) -> Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut pages = vec![String::new()];
+    let mut in_text = false;
+    let mut cell_depth = 0usize;
+
+    loop {
+        let event = reader.read_event().map_err(|e| e.to_string())?;
+        let page = pages.last_mut().expect("at least one page");
+        match event {
+            Event::Start(element) => match element.name().as_ref() {
+                b"w:t" => in_text = true,
+                b"w:tc" => cell_depth += 1,
+                _ => {}
+            },
+            Event::End(element) => match element.name().as_ref() {
+                b"w:t" => in_text = false,
+                /*
+                 ** paragraphs inside a table cell stay on the row's line
+                 */
+                b"w:p" => page.push(if cell_depth > 0 { ' ' } else { '\n' }),
+                b"w:tc" => {
+                    cell_depth = cell_depth.saturating_sub(1);
+                    page.push('\t');
+                }
+                b"w:tr" => page.push('\n'),
+                _ => {}
+            },
+            Event::Empty(element) if is_page_break(&element) && !page.trim().is_empty() => {
+                pages.push(String::new());
+            }
+            Event::Empty(element) => match element.name().as_ref() {
+                b"w:tab" => page.push('\t'),
+                b"w:br" | b"w:cr" => page.push('\n'),
+                _ => {}
+            },
+            Event::Text(text) if in_text => {
+                page.push_str(&text.unescape().map_err(|e| e.to_string())?);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(pages)
+}
+
+/*
+** docProps/core.xml: dc:title, dc:creator, dc:subject, dcterms:created
+*/
+fn docx_metadata(xml: &str) -> DocumentMetadata {
+    let mut metadata = DocumentMetadata::default();
+    let mut reader = Reader::from_str(xml);
+    let mut current: Option<Vec<u8>> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => current = Some(element.name().as_ref().to_vec()),
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Text(text)) => {
+                let Some(value) = text.unescape().ok().map(|value| value.trim().to_string()) else {
+                    continue;
+                };
+                if value.is_empty() {
+                    continue;
+                }
+                match current.as_deref() {
+                    Some(b"dc:title") => metadata.title = Some(value),
+                    Some(b"dc:creator") => metadata.author = Some(value),
+                    Some(b"dc:subject") => metadata.subject = Some(value),
+                    Some(b"dcterms:created") => metadata.created = Some(value),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    metadata
+}
+
+fn docx_pages(bytes: &[u8]) -> Result<(Vec<String>, DocumentMetadata), String> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let document = zip_entry(&mut archive, "word/document.xml")?
+        .ok_or_else(|| "word/document.xml is missing".to_string())?;
+    let pages = docx_text(&document)?;
+    let metadata = zip_entry(&mut archive, "docProps/core.xml")?
+        .map(|core| docx_metadata(&core))
+        .unwrap_or_default();
+    Ok((pages, metadata))
+}
+
+/*
+** everything that is never rendered as text
+*/
+const HTML_SKIPPED: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object",
+];
+
+const HTML_BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+fn html_walk(element: ElementRef, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                /*
+                 ** html collapses runs of whitespace, line breaks included
+                 */
+                for (i, word) in text.split_ascii_whitespace().enumerate() {
+                    if (i > 0 || text.starts_with(|c: char| c.is_ascii_whitespace()))
+                        && !out.ends_with([' ', '\n', '\t'])
+                    {
+                        out.push(' ');
+                    }
+                    out.push_str(word);
+                }
+                if text.ends_with(|c: char| c.is_ascii_whitespace()) && !out.ends_with('\n') {
+                    out.push(' ');
+                }
+            }
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                let name = child.value().name();
+                if HTML_SKIPPED.contains(&name) {
+                    continue;
+                }
+                match name {
+                    "br" => out.push('\n'),
+                    "li" => out.push_str("\n- "),
+                    "tr" => out.push('\n'),
+                    name if HTML_BLOCKS.contains(&name) => out.push('\n'),
+                    _ => {}
+                }
+                html_walk(child, out);
+                match name {
+                    "td" | "th" => out.push('\t'),
+                    name if HTML_BLOCKS.contains(&name) => out.push('\n'),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn html_pages(bytes: &[u8]) -> (Vec<String>, DocumentMetadata) {
+    let html = Html::parse_document(&String::from_utf8_lossy(bytes));
+    let mut text = String::new();
+    html_walk(html.root_element(), &mut text);
+
+    let meta = |name: &str| {
+        html.root_element()
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .find(|element| {
+                element.value().name() == "meta"
+                    && element
+                        .value()
+                        .attr("name")
+                        .is_some_and(|value| value.eq_ignore_ascii_case(name))
+            })
+            .and_then(|element| element.value().attr("content"))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let title = html
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .find(|element| element.value().name() == "title")
+        .map(|element| clean_text(&element.text().collect::<String>()))
+        .filter(|title| !title.is_empty());
+    let metadata = DocumentMetadata {
+        title,
+        author: meta("author"),
+        subject: meta("description"),
+        ..Default::default()
+    };
+    (vec![text], metadata)
+}
+
+/*
+** utf-8, or utf-16 with a byte order mark. form feeds split pages, the way
+** text exports of paged documents mark them.
+*/
+fn text_pages(bytes: &[u8]) -> Vec<String> {
+    let text = if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<u16>>(),
+        )
+    } else if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect::<Vec<u16>>(),
+        )
+    } else {
+        String::from_utf8_lossy(bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes)).to_string()
+    };
+    text.split('\u{000c}').map(str::to_string).collect()
+}
+
+/*
+** read `path` as text, page by page. `max_chars` caps the returned text
+** (pages past the cap are dropped) for callers feeding a prompt.
+*/
+pub fn extract_document(
+    path: &Path,
+    max_chars: Option<usize>,
+) -> Result<ExtractedDocument, RuntimeError> {
+    let failed = |reason: String| RuntimeError::ExtractionFailed {
+        path: path.display().to_string(),
+        reason,
+    };
+    if !path.is_absolute() {
+        return Err(RuntimeError::InvalidInput {
+            reason: format!("Document path must be absolute: {}", path.display()),
+        });
+    }
+    let size_bytes = std::fs::metadata(path)
+        .map_err(|e| failed(e.to_string()))?
+        .len();
+    if size_bytes > MAX_DOCUMENT_BYTES {
+        return Err(failed(format!(
+            "the file is larger than {} MB",
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        )));
+    }
+    let bytes = std::fs::read(path).map_err(|e| failed(e.to_string()))?;
+
+    let kind = detect_kind(path, &bytes).ok_or_else(|| RuntimeError::Unsupported {
+        reason: format!("{} is not a PDF, DOCX, HTML or text file", path.display()),
+    })?;
+    let (raw_pages, mut metadata) = match kind {
+        /*
+         ** the PDF parser panics on some malformed files instead of failing
+         */
+        DocumentKind::Pdf => panic::catch_unwind(AssertUnwindSafe(|| pdf_pages(&bytes)))
+            .map_err(|_| failed("the PDF could not be parsed".to_string()))?
+            .map_err(failed)?,
+        DocumentKind::Docx => docx_pages(&bytes).map_err(failed)?,
+        DocumentKind::Html => html_pages(&bytes),
+        DocumentKind::Text => (text_pages(&bytes), DocumentMetadata::default()),
+    };
+
+    let mut pages: Vec<DocumentPage> = raw_pages
+        .iter()
+        .enumerate()
+        .map(|(i, raw)| DocumentPage {
+            number: i as u32 + 1,
+            text: clean_text(raw),
+        })
+        .collect();
+    metadata.page_count = pages.len();
+    metadata.word_count = pages
+        .iter()
+        .map(|page| page.text.split_whitespace().count())
+        .sum();
+
+    let mut truncated = false;
+    if let Some(max_chars) = max_chars {
+        let mut budget = max_chars;
+        let mut kept = 0;
+        for page in pages.iter_mut() {
+            let chars = page.text.chars().count();
+            if chars > budget {
+                page.text = page.text.chars().take(budget).collect();
+                truncated = true;
+                kept += usize::from(budget > 0);
+                break;
+            }
+            budget -= chars;
+            kept += 1;
+        }
+        truncated |= kept < pages.len();
+        pages.truncate(kept);
+    }
+
+    let text = pages
+        .iter()
+        .map(|page| page.text.as_str())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(ExtractedDocument {
+        path: path.display().to_string(),
+        kind,
+        mime_type: kind.mime_type().to_string(),
+        size_bytes,
+        metadata,
+        pages,
+        text,
+        truncated,
+    })
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::browser_manager::managed_cdp_session;
+use crate::cdp::{CdpEvent, SetDownloadBehavior};
+use crate::error::RuntimeError;
+use crate::events::{emit, DOWNLOAD_PROGRESS};
+use crate::paths::app_data_subdir;
+use crate::registry::BROWSER_MANAGER;
+use crate::utils::{mime_type_for_path, now_ms};
+
+const DOWNLOADS_DIR: &str = "downloads";
+const PROGRESS_EMIT_INTERVAL_MS: u64 = 250;
+const DEFAULT_WAIT_TIMEOUT_MS: u64 = 60_000;
+const MAX_FILE_NAME_LEN: usize = 120;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    InProgress,
+    Completed,
+    Canceled,
+}
+
+/*
+** `path`, `mime_type` and `size_bytes` are known once the download completed
+** and the file was given its suggested name. `total_bytes` is 0 when the
+** server did not say.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Download {
+    pub guid: String,
+    pub instance_id: String,
+    pub url: String,
+    pub suggested_filename: String,
+    pub state: DownloadState,
+    pub received_bytes: u64,
+    pub total_bytes: u64,
+    pub path: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<u64>,
+    pub started_at_ms: u64,
+    pub finished_at_ms: Option<u64>,
+}
+
+struct TrackedDownload {
+    download: Download,
+    /*
+     ** handed to a wait_for_download caller already
+     */
+    claimed: bool,
+    last_emitted_ms: u64,
+}
+
+struct InstanceDownloads {
+    folder: PathBuf,
+    /*
+     ** ids of the CDP session we listen on and of the one the download
+     ** behavior was set on; a reconnect or a relaunch brings a new session
+     ** that has to be told again
+     */
+    listening: Option<u64>,
+    applied: Option<u64>,
+    downloads: Vec<TrackedDownload>,
+}
+
+static DOWNLOADS: Lazy<Mutex<HashMap<String, InstanceDownloads>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/*
+** every download that finished or was canceled, for wait_for_download
+*/
+static FINISHED: Lazy<broadcast::Sender<Download>> = Lazy::new(|| broadcast::channel(64).0);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadWillBegin {
+    guid: String,
+    url: String,
+    suggested_filename: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress {
+    guid: String,
+    total_bytes: f64,
+    received_bytes: f64,
+    state: String,
+}
+
+/*
+** ids default to the selected instance. downloads land on the browser's
+** machine, so a remote browser's files would never reach us.
+*/
+async fn resolve_instance(instance_id: Option<&str>) -> Result<String, RuntimeError> {
+    let instance = BROWSER_MANAGER.snapshot(instance_id).await?;
+    if instance
+        .endpoint
+        .as_ref()
+        .is_some_and(|endpoint| !endpoint.is_local())
+    {
+        return Err(RuntimeError::Unsupported {
+            reason: "Downloads can only be managed for browsers on this machine".to_string(),
+        });
+    }
+    Ok(instance.instance_id)
+}
+
+/*
+** route the instance's downloads into `folder` (default
+** `<app data>/downloads/<instance id>`) and start tracking them
+*/
+pub async fn enable_downloads(
+    instance_id: Option<&str>,
+    folder: Option<&str>,
+) -> Result<PathBuf, RuntimeError> {
+    let instance_id = resolve_instance(instance_id).await?;
+    let folder = match folder {
+        Some(folder) if !Path::new(folder).is_absolute() => {
+            return Err(RuntimeError::InvalidInput {
+                reason: format!("Download folder must be an absolute path: {folder}"),
+            });
+        }
+        Some(folder) => PathBuf::from(folder),
+        None => app_data_subdir(DOWNLOADS_DIR)?.join(&instance_id),
+    };
+    std::fs::create_dir_all(&folder).map_err(|e| RuntimeError::storage(&folder, e))?;
+
+    {
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let entry = downloads
+            .entry(instance_id.clone())
+            .or_insert_with(|| InstanceDownloads {
+                folder: folder.clone(),
+                listening: None,
+                applied: None,
+                downloads: Vec::new(),
+            });
+        entry.folder = folder.clone();
+    }
+    watch(&instance_id, true).await?;
+    info!(%instance_id, folder = %folder.display(), "managing downloads");
+    Ok(folder)
+}
+
+/*
+** listen for the instance's download events on its current session, and
+** (re)apply the download behavior if that session is new or `reapply` is set
+*/
+async fn watch(instance_id: &str, reapply: bool) -> Result<(), RuntimeError> {
+    let session = managed_cdp_session(Some(instance_id)).await?;
+    let (folder, is_new, is_applied) = {
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let Some(entry) = downloads.get_mut(instance_id) else {
+            return Ok(());
+        };
+        let is_new = entry.listening != Some(session.id());
+        entry.listening = Some(session.id());
+        (
+            entry.folder.clone(),
+            is_new,
+            entry.applied == Some(session.id()),
+        )
+    };
+
+    /*
+     ** subscribe before the browser starts reporting
+     */
+    if is_new {
+        let events = session.subscribe();
+        let span = info_span!("instance", %instance_id);
+        tokio::spawn(listen(instance_id.to_string(), session.id(), events).instrument(span));
+    }
+
+    if is_applied && !reapply {
+        return Ok(());
+    }
+    session
+        .execute(&SetDownloadBehavior {
+            behavior: "allowAndName",
+            download_path: folder.to_string_lossy().to_string(),
+            events_enabled: true,
+        })
+        .await?;
+    if let Some(entry) = DOWNLOADS.lock().unwrap().get_mut(instance_id) {
+        entry.applied = Some(session.id());
+    }
+    Ok(())
+}
+
+/*
+** browsers we launch are the agent's, so their downloads are always ours.
+** called after a launch and after the watchdog brought one back.
+*/
+pub fn manage_launched(instance_id: String) {
+    let span = info_span!("instance", %instance_id);
+    tokio::spawn(
+        async move {
+            let known = DOWNLOADS.lock().unwrap().contains_key(&instance_id);
+            let managed = if known {
+                watch(&instance_id, false).await
+            } else {
+                enable_downloads(Some(&instance_id), None).await.map(|_| ())
+            };
+            if let Err(e) = managed {
+                warn!("downloads of browser {instance_id} are not managed: {e}");
+            }
+        }
+        .instrument(span),
+    );
+}
+
+fn is_current(instance_id: &str, session: u64) -> bool {
+    DOWNLOADS
+        .lock()
+        .unwrap()
+        .get(instance_id)
+        .is_some_and(|entry| entry.listening == Some(session))
+}
+
+/*
+** runs until the session closes or is replaced by a newer one
+*/
+async fn listen(instance_id: String, session: u64, mut events: broadcast::Receiver<CdpEvent>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("download listener missed {skipped} CDP events");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if event.session_id.is_some() || !event.method.starts_with("Browser.download") {
+            continue;
+        }
+        if !is_current(&instance_id, session) {
+            break;
+        }
+        match event.method.as_str() {
+            "Browser.downloadWillBegin" => match serde_json::from_value(event.params) {
+                Ok(begin) => begin_download(&instance_id, begin),
+                Err(e) => warn!("malformed Browser.downloadWillBegin: {e}"),
+            },
+            "Browser.downloadProgress" => match serde_json::from_value(event.params) {
+                Ok(progress) => update_download(&instance_id, progress),
+                Err(e) => warn!("malformed Browser.downloadProgress: {e}"),
+            },
+            _ => {}
+        }
+    }
+    debug!("download listener of browser {instance_id} stopped");
+}
+
+fn begin_download(instance_id: &str, begin: DownloadWillBegin) {
+    let download = Download {
+        guid: begin.guid,
+        instance_id: instance_id.to_string(),
+        url: begin.url,
+        suggested_filename: begin.suggested_filename,
+        state: DownloadState::InProgress,
+        received_bytes: 0,
+        total_bytes: 0,
+        path: None,
+        mime_type: None,
+        size_bytes: None,
+        started_at_ms: now_ms(),
+        finished_at_ms: None,
+    };
+    info!(guid = %download.guid, url = %download.url, "download started");
+    {
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let Some(entry) = downloads.get_mut(instance_id) else {
+            return;
+        };
+        entry.downloads.push(TrackedDownload {
+            download: download.clone(),
+            claimed: false,
+            last_emitted_ms: download.started_at_ms,
+        });
+    }
+    emit(DOWNLOAD_PROGRESS, download);
+}
+
+fn update_download(instance_id: &str, progress: DownloadProgress) {
+    let state = match progress.state.as_str() {
+        "completed" => DownloadState::Completed,
+        "canceled" => DownloadState::Canceled,
+        _ => DownloadState::InProgress,
+    };
+    let now = now_ms();
+
+    let (download, folder) = {
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let Some(entry) = downloads.get_mut(instance_id) else {
+            return;
+        };
+        let folder = entry.folder.clone();
+        let Some(tracked) = entry
+            .downloads
+            .iter_mut()
+            .find(|tracked| tracked.download.guid == progress.guid)
+        else {
+            return;
+        };
+        /*
+         ** progress arrives per chunk; the UI only needs a few updates a second
+         */
+        if state == DownloadState::InProgress
+            && now.saturating_sub(tracked.last_emitted_ms) < PROGRESS_EMIT_INTERVAL_MS
+        {
+            tracked.download.received_bytes = progress.received_bytes as u64;
+            return;
+        }
+        tracked.last_emitted_ms = now;
+        tracked.download.state = state;
+        tracked.download.received_bytes = progress.received_bytes as u64;
+        tracked.download.total_bytes = progress.total_bytes as u64;
+        if state != DownloadState::InProgress {
+            tracked.download.finished_at_ms = Some(now);
+        }
+        (tracked.download.clone(), folder)
+    };
+
+    let download = match state {
+        DownloadState::InProgress => download,
+        DownloadState::Completed => finish_download(instance_id, download, &folder),
+        DownloadState::Canceled => {
+            info!(guid = %download.guid, "download canceled");
+            download
+        }
+    };
+    emit(DOWNLOAD_PROGRESS, download.clone());
+    if download.state != DownloadState::InProgress {
+        let _ = FINISHED.send(download);
+    }
+}
+
+/*
+** give the file its suggested name and record what it turned out to be
+*/
+fn finish_download(instance_id: &str, mut download: Download, folder: &Path) -> Download {
+    let saved = folder.join(&download.guid);
+    let name = unique_file_name(folder, &file_name(&download.suggested_filename));
+    let path = match std::fs::rename(&saved, folder.join(&name)) {
+        Ok(()) => folder.join(&name),
+        Err(e) => {
+            warn!("keeping download as {}: {e}", saved.display());
+            saved
+        }
+    };
+    download.size_bytes = std::fs::metadata(&path).map(|meta| meta.len()).ok();
+    download.mime_type = Some(mime_type_for_path(&path).to_string());
+    download.path = Some(path.to_string_lossy().to_string());
+    info!(
+        guid = %download.guid,
+        path = %path.display(),
+        size_bytes = download.size_bytes,
+        "download completed"
+    );
+
+    let mut downloads = DOWNLOADS.lock().unwrap();
+    if let Some(tracked) = downloads.get_mut(instance_id).and_then(|entry| {
+        entry
+            .downloads
+            .iter_mut()
+            .find(|tracked| tracked.download.guid == download.guid)
+    }) {
+        tracked.download = download.clone();
+    }
+    download
+}
+
+/*
+** the suggested name comes from the server; keep it from leaving the folder.
+** long names lose the end of their stem, never the extension
+*/
+fn file_name(suggested: &str) -> String {
+    let name: String = suggested
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        return "download".to_string();
+    }
+    if name.chars().count() <= MAX_FILE_NAME_LEN {
+        return name.to_string();
+    }
+    match split_extension(name) {
+        Some((stem, extension)) if extension.chars().count() < MAX_FILE_NAME_LEN / 2 => {
+            let keep = MAX_FILE_NAME_LEN - extension.chars().count() - 1;
+            let stem: String = stem.chars().take(keep).collect();
+            format!("{}.{extension}", stem.trim_end())
+        }
+        _ => name.chars().take(MAX_FILE_NAME_LEN).collect(),
+    }
+}
+
+/*
+** "report.pdf" -> ("report", "pdf"); dotfiles and bare names have none
+*/
+fn split_extension(name: &str) -> Option<(&str, &str)> {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => {
+            Some((stem, extension))
+        }
+        _ => None,
+    }
+}
+
+/*
+** "report.pdf", "report (2).pdf", ...
+*/
+fn unique_file_name(folder: &Path, name: &str) -> String {
+    if !folder.join(name).exists() {
+        return name.to_string();
+    }
+    let (stem, extension) = match split_extension(name) {
+        Some((stem, extension)) => (stem, Some(extension)),
+        None => (name, None),
+    };
+    (2..)
+        .map(|n| match extension {
+            Some(extension) => format!("{stem} ({n}).{extension}"),
+            None => format!("{stem} ({n})"),
+        })
+        .find(|candidate| !folder.join(candidate).exists())
+        .unwrap_or_else(|| name.to_string())
+}
+
+/*
+** completed downloads, oldest first; `include_in_progress` adds the ones
+** still running and the canceled ones
+*/
+pub async fn list_downloads(
+    instance_id: Option<&str>,
+    include_in_progress: bool,
+) -> Result<Vec<Download>, RuntimeError> {
+    let instance_id = BROWSER_MANAGER.snapshot(instance_id).await?.instance_id;
+    let downloads = DOWNLOADS.lock().unwrap();
+    Ok(downloads
+        .get(&instance_id)
+        .map(|entry| {
+            entry
+                .downloads
+                .iter()
+                .map(|tracked| &tracked.download)
+                .filter(|download| {
+                    include_in_progress || download.state == DownloadState::Completed
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/*
+** hand out the oldest finished download nobody waited for yet
+*/
+fn claim(instance_id: &str, since_ms: u64) -> Option<Download> {
+    let mut downloads = DOWNLOADS.lock().unwrap();
+    let tracked = downloads
+        .get_mut(instance_id)?
+        .downloads
+        .iter_mut()
+        .find(|tracked| {
+            !tracked.claimed
+                && tracked.download.state != DownloadState::InProgress
+                && tracked.download.started_at_ms >= since_ms
+        })?;
+    tracked.claimed = true;
+    Some(tracked.download.clone())
+}
+
+/*
+** wait for the next download of the instance to finish, as a skill step
+** after the click that starts it. a download that already finished before
+** the call counts as long as no earlier wait returned it; `since_ms` also
+** skips downloads that started before then. canceled downloads are returned
+** too, check `state`.
+*/
+pub async fn wait_for_download(
+    instance_id: Option<&str>,
+    timeout_ms: Option<u64>,
+    since_ms: Option<u64>,
+) -> Result<Download, RuntimeError> {
+    let instance_id = resolve_instance(instance_id).await?;
+    let known = DOWNLOADS.lock().unwrap().contains_key(&instance_id);
+    if known {
+        watch(&instance_id, false).await?;
+    } else {
+        enable_downloads(Some(&instance_id), None).await?;
+    }
+
+    let timeout_ms = timeout_ms.unwrap_or(DEFAULT_WAIT_TIMEOUT_MS);
+    let since_ms = since_ms.unwrap_or(0);
+    let mut finished = FINISHED.subscribe();
+    let waited = tokio::time::timeout(Duration::from_millis(timeout_ms), async {
+        loop {
+            if let Some(download) = claim(&instance_id, since_ms) {
+                return download;
+            }
+            /*
+             ** anything that finished, for any instance, is a reason to look again
+             */
+            let _ = finished.recv().await;
+        }
+    })
+    .await;
+    waited.map_err(|_| RuntimeError::DownloadTimeout {
+        instance_id,
+        timeout_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_cannot_leave_the_folder() {
+        for (suggested, expected) in [
+            ("report.pdf", "report.pdf"),
+            ("../../etc/passwd", "_.._etc_passwd"),
+            ("..\\windows\\system.ini", "_windows_system.ini"),
+            ("a/b:c*d?.txt", "a_b_c_d_.txt"),
+            (".bashrc", "bashrc"),
+            ("...hidden.tar.gz", "hidden.tar.gz"),
+            ("  spaced.csv  ", "spaced.csv"),
+            ("tab\there.txt", "tab_here.txt"),
+            ("", "download"),
+            ("...", "download"),
+        ] {
+            assert_eq!(file_name(suggested), expected, "{suggested:?}");
+        }
+    }
+
+    #[test]
+    fn long_names_keep_their_extension() {
+        let name = file_name(&format!("{}.pdf", "a".repeat(300)));
+        assert_eq!(name.chars().count(), MAX_FILE_NAME_LEN);
+        assert!(name.ends_with("a.pdf"));
+
+        let name = file_name(&format!("{}.tar.gz", "é".repeat(300)));
+        assert_eq!(name.chars().count(), MAX_FILE_NAME_LEN);
+        assert!(name.ends_with("é.gz"));
+
+        /*
+         ** an "extension" that is most of the name is just more name
+         */
+        let name = file_name(&format!("x.{}", "b".repeat(300)));
+        assert_eq!(name, format!("x.{}", "b".repeat(MAX_FILE_NAME_LEN - 2)));
+    }
+
+    #[test]
+    fn taken_names_get_a_counter() {
+        let folder = std::env::temp_dir().join(format!("runtime-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        assert_eq!(unique_file_name(&folder, "report.pdf"), "report.pdf");
+        for taken in ["report.pdf", "report (2).pdf", "notes", ".env"] {
+            std::fs::write(folder.join(taken), b"").unwrap();
+        }
+        assert_eq!(unique_file_name(&folder, "report.pdf"), "report (3).pdf");
+        assert_eq!(unique_file_name(&folder, "notes"), "notes (2)");
+        assert_eq!(unique_file_name(&folder, ".env"), ".env (2)");
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::RuntimeError;
+
+/*
+** what browsers listen on when started with a bare --remote-debugging-port
+*/
+const DEFAULT_PORT: u16 = 9222;
+const DEVTOOLS_PATH: &str = "/devtools/";
+
+fn invalid(input: &str, reason: impl fmt::Display) -> RuntimeError {
+    RuntimeError::InvalidEndpoint {
+        input: input.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/*
+** where a DevTools server can be reached. `base_path` and `query` are kept
+** for endpoints behind a proxy ("https://host/chrome/?token=..."); `ws_path`
+** is the browser target ("/devtools/browser/<id>") once we know it.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CdpEndpoint {
+    pub secure: bool,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub base_path: String,
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub ws_path: Option<String>,
+}
+
+impl CdpEndpoint {
+    pub fn local(port: u16) -> Self {
+        Self {
+            secure: false,
+            host: "127.0.0.1".to_string(),
+            port,
+            base_path: String::new(),
+            query: None,
+            ws_path: None,
+        }
+    }
+
+    /*
+     ** accepts ws/wss/http/https URLs and bare "host[:port]". an explicit
+     ** scheme without a port means that scheme's default port; a bare host
+     ** means the usual DevTools port.
+     */
+    pub fn parse(input: &str) -> Result<Self, RuntimeError> {
+        let input = input.trim();
+        let has_scheme = input.contains("://");
+        let url = if has_scheme {
+            Url::parse(input)
+        } else {
+            Url::parse(&format!("http://{input}"))
+        }
+        .map_err(|e| invalid(input, e))?;
+
+        let secure = match url.scheme() {
+            "ws" | "http" => false,
+            "wss" | "https" => true,
+            scheme => return Err(invalid(input, format!("unsupported scheme '{scheme}'"))),
+        };
+        let host = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| invalid(input, "no host"))?
+            .to_string();
+        let port = if has_scheme {
+            url.port_or_known_default()
+        } else {
+            url.port()
+        }
+        .unwrap_or(DEFAULT_PORT);
+
+        let path = url.path().trim_end_matches('/');
+        let (base_path, ws_path) = match path.find(DEVTOOLS_PATH) {
+            Some(at) => (&path[..at], Some(path[at..].to_string())),
+            None => (path, None),
+        };
+
+        Ok(Self {
+            secure,
+            host,
+            port,
+            base_path: base_path.to_string(),
+            query: url.query().map(str::to_string),
+            ws_path,
+        })
+    }
+
+    /*
+     ** the same server with the target path from a ws url the browser
+     ** reported. the browser only knows its own view of host and port, which
+     ** is wrong behind Docker port mappings and proxies, so only the path is taken.
+     */
+    pub fn with_ws_url(&self, reported: &str) -> Result<Self, RuntimeError> {
+        let ws_path = Self::parse(reported)?
+            .ws_path
+            .ok_or_else(|| invalid(reported, "no DevTools target path"))?;
+        Ok(Self {
+            ws_path: Some(ws_path),
+            ..self.clone()
+        })
+    }
+
+    fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    fn with_query(&self, url: String) -> String {
+        match &self.query {
+            Some(query) if url.contains('?') => format!("{url}&{query}"),
+            Some(query) => format!("{url}?{query}"),
+            None => url,
+        }
+    }
+
+    /*
+     ** `path` is relative to the server root, e.g. "/json/version"
+     */
+    pub fn http_url(&self, path: &str) -> String {
+        let scheme = if self.secure { "https" } else { "http" };
+        self.with_query(format!(
+            "{scheme}://{}{}{path}",
+            self.authority(),
+            self.base_path
+        ))
+    }
+
+    pub fn ws_url(&self) -> Option<String> {
+        let scheme = if self.secure { "wss" } else { "ws" };
+        let ws_path = self.ws_path.as_deref()?;
+        Some(self.with_query(format!(
+            "{scheme}://{}{}{ws_path}",
+            self.authority(),
+            self.base_path
+        )))
+    }
+
+    pub fn is_local(&self) -> bool {
+        is_local_host(&self.host)
+    }
+}
+
+/*
+** "localhost" or a loopback address, bracketed IPv6 included
+*/
+pub fn is_local_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+impl fmt::Display for CdpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ws_url() {
+            Some(ws_url) => f.write_str(&ws_url),
+            None => f.write_str(&self.http_url("")),
+        }
+    }
+}
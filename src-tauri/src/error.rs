@@ -0,0 +1,382 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+
+/*
+** what commands hand back to the frontend: { code, message, details }.
+** `code` is stable and meant to be matched on; `message` is for humans and
+** may change; `details` carries the variant's fields.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    InvalidInput {
+        reason: String,
+    },
+    NoBrowserAvailable,
+    BrowserNotFound {
+        path: String,
+    },
+    InstanceNotFound {
+        instance_id: String,
+    },
+    NoInstanceSelected,
+    ProfileInUse {
+        profile: String,
+        instance_id: String,
+    },
+    LaunchInProgress {
+        profile: String,
+    },
+    ProfileNotFound {
+        profile: String,
+    },
+    ProfileExists {
+        profile: String,
+    },
+    ProfileBrowserMismatch {
+        profile: String,
+        owner: String,
+        requested: String,
+    },
+    CustomBrowserNotFound {
+        id: String,
+    },
+    CustomBrowserExists {
+        id: String,
+    },
+    PortOccupied {
+        port: u16,
+        owner: String,
+    },
+    BrowserMismatch {
+        selected: String,
+        running: String,
+    },
+    InvalidEndpoint {
+        input: String,
+        reason: String,
+    },
+    EndpointUnreachable {
+        url: String,
+        reason: String,
+    },
+    LaunchFailed {
+        path: String,
+        reason: String,
+    },
+    LaunchTimeout {
+        seconds: u64,
+    },
+    BrowserExited {
+        status: String,
+    },
+    CdpUnavailable {
+        reason: String,
+    },
+    CdpCommandFailed {
+        reason: String,
+    },
+    Unsupported {
+        reason: String,
+    },
+    SkillDownload {
+        domain: String,
+        reason: String,
+    },
+    SkillParse {
+        domain: String,
+        reason: String,
+    },
+    AppCallFailed {
+        function: String,
+        reason: String,
+    },
+    ArtifactNotFound {
+        session_id: String,
+        artifact_id: String,
+    },
+    DownloadTimeout {
+        instance_id: String,
+        timeout_ms: u64,
+    },
+    ExtractionFailed {
+        path: String,
+        reason: String,
+    },
+    SettingsFailed {
+        key: String,
+        reason: String,
+    },
+    StorageFailed {
+        path: String,
+        reason: String,
+    },
+    Internal {
+        message: String,
+    },
+}
+
+impl RuntimeError {
+    pub fn internal(message: impl fmt::Display) -> Self {
+        RuntimeError::Internal {
+            message: message.to_string(),
+        }
+    }
+
+    /*
+     ** CdpSession reports protocol errors as strings that already name the method
+     */
+    pub fn cdp(reason: String) -> Self {
+        RuntimeError::CdpCommandFailed { reason }
+    }
+
+    /*
+     ** a file or folder under app data could not be read or written
+     */
+    pub fn storage(path: &std::path::Path, reason: impl fmt::Display) -> Self {
+        RuntimeError::StorageFailed {
+            path: path.display().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn unreachable(url: &str, reason: impl fmt::Display) -> Self {
+        RuntimeError::EndpointUnreachable {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::InvalidInput { .. } => "InvalidInput",
+            RuntimeError::NoBrowserAvailable => "NoBrowserAvailable",
+            RuntimeError::BrowserNotFound { .. } => "BrowserNotFound",
+            RuntimeError::InstanceNotFound { .. } => "InstanceNotFound",
+            RuntimeError::NoInstanceSelected => "NoInstanceSelected",
+            RuntimeError::ProfileInUse { .. } => "ProfileInUse",
+            RuntimeError::LaunchInProgress { .. } => "LaunchInProgress",
+            RuntimeError::ProfileNotFound { .. } => "ProfileNotFound",
+            RuntimeError::ProfileExists { .. } => "ProfileExists",
+            RuntimeError::ProfileBrowserMismatch { .. } => "ProfileBrowserMismatch",
+            RuntimeError::CustomBrowserNotFound { .. } => "CustomBrowserNotFound",
+            RuntimeError::CustomBrowserExists { .. } => "CustomBrowserExists",
+            RuntimeError::PortOccupied { .. } => "PortOccupied",
+            RuntimeError::BrowserMismatch { .. } => "BrowserMismatch",
+            RuntimeError::InvalidEndpoint { .. } => "InvalidEndpoint",
+            RuntimeError::EndpointUnreachable { .. } => "EndpointUnreachable",
+            RuntimeError::LaunchFailed { .. } => "LaunchFailed",
+            RuntimeError::LaunchTimeout { .. } => "LaunchTimeout",
+            RuntimeError::BrowserExited { .. } => "BrowserExited",
+            RuntimeError::CdpUnavailable { .. } => "CdpUnavailable",
+            RuntimeError::CdpCommandFailed { .. } => "CdpCommandFailed",
+            RuntimeError::Unsupported { .. } => "Unsupported",
+            RuntimeError::SkillDownload { .. } => "SkillDownload",
+            RuntimeError::SkillParse { .. } => "SkillParse",
+            RuntimeError::AppCallFailed { .. } => "AppCallFailed",
+            RuntimeError::ArtifactNotFound { .. } => "ArtifactNotFound",
+            RuntimeError::DownloadTimeout { .. } => "DownloadTimeout",
+            RuntimeError::ExtractionFailed { .. } => "ExtractionFailed",
+            RuntimeError::SettingsFailed { .. } => "SettingsFailed",
+            RuntimeError::StorageFailed { .. } => "StorageFailed",
+            RuntimeError::Internal { .. } => "Internal",
+        }
+    }
+
+    pub fn details(&self) -> Value {
+        match self {
+            RuntimeError::NoBrowserAvailable | RuntimeError::NoInstanceSelected => Value::Null,
+            RuntimeError::BrowserNotFound { path } => json!({ "path": path }),
+            RuntimeError::InstanceNotFound { instance_id } => {
+                json!({ "instance_id": instance_id })
+            }
+            RuntimeError::ProfileInUse {
+                profile,
+                instance_id,
+            } => json!({ "profile": profile, "instance_id": instance_id }),
+            RuntimeError::LaunchInProgress { profile }
+            | RuntimeError::ProfileNotFound { profile }
+            | RuntimeError::ProfileExists { profile } => json!({ "profile": profile }),
+            RuntimeError::ProfileBrowserMismatch {
+                profile,
+                owner,
+                requested,
+            } => json!({ "profile": profile, "owner": owner, "requested": requested }),
+            RuntimeError::CustomBrowserNotFound { id }
+            | RuntimeError::CustomBrowserExists { id } => json!({ "id": id }),
+            RuntimeError::PortOccupied { port, owner } => json!({ "port": port, "owner": owner }),
+            RuntimeError::BrowserMismatch { selected, running } => {
+                json!({ "selected": selected, "running": running })
+            }
+            RuntimeError::InvalidEndpoint { input, reason } => {
+                json!({ "input": input, "reason": reason })
+            }
+            RuntimeError::EndpointUnreachable { url, reason } => {
+                json!({ "url": url, "reason": reason })
+            }
+            RuntimeError::LaunchFailed { path, reason } => {
+                json!({ "path": path, "reason": reason })
+            }
+            RuntimeError::LaunchTimeout { seconds } => json!({ "seconds": seconds }),
+            RuntimeError::BrowserExited { status } => json!({ "status": status }),
+            RuntimeError::CdpUnavailable { reason }
+            | RuntimeError::CdpCommandFailed { reason }
+            | RuntimeError::Unsupported { reason }
+            | RuntimeError::InvalidInput { reason } => {
+                json!({ "reason": reason })
+            }
+            RuntimeError::SkillDownload { domain, reason }
+            | RuntimeError::SkillParse { domain, reason } => {
+                json!({ "domain": domain, "reason": reason })
+            }
+            RuntimeError::AppCallFailed { function, reason } => {
+                json!({ "function": function, "reason": reason })
+            }
+            RuntimeError::ArtifactNotFound {
+                session_id,
+                artifact_id,
+            } => json!({ "session_id": session_id, "artifact_id": artifact_id }),
+            RuntimeError::DownloadTimeout {
+                instance_id,
+                timeout_ms,
+            } => json!({ "instance_id": instance_id, "timeout_ms": timeout_ms }),
+            RuntimeError::ExtractionFailed { path, reason }
+            | RuntimeError::StorageFailed { path, reason } => {
+                json!({ "path": path, "reason": reason })
+            }
+            RuntimeError::SettingsFailed { key, reason } => {
+                json!({ "key": key, "reason": reason })
+            }
+            RuntimeError::Internal { .. } => Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::InvalidInput { reason } => f.write_str(reason),
+            RuntimeError::NoBrowserAvailable => write!(
+                f,
+                "No browsers found. Please install Google Chrome, Microsoft Edge, or Arc"
+            ),
+            RuntimeError::BrowserNotFound { path } => {
+                write!(f, "Browser not found in available browsers: {path}")
+            }
+            RuntimeError::InstanceNotFound { instance_id } => {
+                write!(f, "Unknown browser instance: {instance_id}")
+            }
+            RuntimeError::NoInstanceSelected => write!(f, "No browser instance selected"),
+            RuntimeError::ProfileInUse {
+                profile,
+                instance_id,
+            } => write!(
+                f,
+                "Profile '{profile}' is already open in browser instance {instance_id}"
+            ),
+            RuntimeError::LaunchInProgress { profile } => {
+                write!(f, "Profile '{profile}' is already being launched")
+            }
+            RuntimeError::ProfileNotFound { profile } => {
+                write!(f, "Profile '{profile}' not found")
+            }
+            RuntimeError::ProfileExists { profile } => {
+                write!(f, "Profile '{profile}' already exists")
+            }
+            RuntimeError::ProfileBrowserMismatch {
+                profile,
+                owner,
+                requested,
+            } => write!(f, "Profile '{profile}' belongs to {owner}, not {requested}"),
+            RuntimeError::CustomBrowserNotFound { id } => {
+                write!(f, "No custom browser with id '{id}'")
+            }
+            RuntimeError::CustomBrowserExists { id } => {
+                write!(f, "A custom browser with id '{id}' already exists")
+            }
+            RuntimeError::PortOccupied { port, owner } => write!(
+                f,
+                "Port {port} is already occupied by {owner}. Close it and retry."
+            ),
+            RuntimeError::BrowserMismatch { selected, running } => {
+                write!(
+                    f,
+                    "Browser mismatch! Selected: {selected}, Running: {running}"
+                )
+            }
+            RuntimeError::InvalidEndpoint { input, reason } => {
+                write!(f, "Invalid DevTools endpoint '{input}': {reason}")
+            }
+            RuntimeError::EndpointUnreachable { url, reason } => {
+                write!(f, "DevTools endpoint {url} is not reachable: {reason}")
+            }
+            RuntimeError::LaunchFailed { path, reason } => {
+                write!(f, "Failed to launch browser {path}: {reason}")
+            }
+            RuntimeError::LaunchTimeout { seconds } => {
+                write!(
+                    f,
+                    "Browser did not report a DevTools endpoint within {seconds}s"
+                )
+            }
+            RuntimeError::BrowserExited { status } => write!(
+                f,
+                "Browser process exited before DevTools was ready (status: {status})"
+            ),
+            RuntimeError::CdpUnavailable { reason } => {
+                write!(f, "DevTools connection unavailable: {reason}")
+            }
+            RuntimeError::CdpCommandFailed { reason } => f.write_str(reason),
+            RuntimeError::Unsupported { reason } => f.write_str(reason),
+            RuntimeError::SkillDownload { domain, reason } => {
+                write!(f, "Failed to download skill file for {domain}: {reason}")
+            }
+            RuntimeError::SkillParse { domain, reason } => {
+                write!(f, "Failed to parse skill file for {domain}: {reason}")
+            }
+            RuntimeError::AppCallFailed { function, reason } => {
+                write!(f, "App function {function} failed: {reason}")
+            }
+            RuntimeError::ArtifactNotFound {
+                session_id,
+                artifact_id,
+            } => write!(f, "No artifact {artifact_id} in session {session_id}"),
+            RuntimeError::DownloadTimeout {
+                instance_id,
+                timeout_ms,
+            } => write!(
+                f,
+                "No download finished in browser instance {instance_id} within {timeout_ms}ms"
+            ),
+            RuntimeError::ExtractionFailed { path, reason } => {
+                write!(f, "Failed to extract text from {path}: {reason}")
+            }
+            RuntimeError::SettingsFailed { key, reason } => {
+                write!(f, "Failed to save setting {key}: {reason}")
+            }
+            RuntimeError::StorageFailed { path, reason } => {
+                write!(f, "Failed to access {path}: {reason}")
+            }
+            RuntimeError::Internal { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl Serialize for RuntimeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("RuntimeError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+impl From<RuntimeError> for String {
+    fn from(error: RuntimeError) -> Self {
+        error.to_string()
+    }
+}
@@ -0,0 +1,46 @@
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tracing::warn;
+
+use crate::error::RuntimeError;
+
+pub const BROWSER_LAUNCHED: &str = "browser://launched";
+pub const BROWSER_CRASHED: &str = "browser://crashed";
+pub const BROWSER_RECONNECTED: &str = "browser://reconnected";
+pub const DOWNLOAD_PROGRESS: &str = "download://progress";
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+
+/*
+** keep a handle around so background tasks can talk to the webview
+*/
+pub fn init(app: &AppHandle) {
+    let _ = APP_HANDLE.set(app.clone());
+}
+
+pub fn app_handle() -> Result<&'static AppHandle, RuntimeError> {
+    APP_HANDLE
+        .get()
+        .ok_or_else(|| RuntimeError::internal("App handle is not initialised yet"))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BrowserLifecycleEvent {
+    pub instance_id: String,
+    pub browser_id: String,
+    pub ws_url: Option<String>,
+    pub reason: Option<String>,
+    pub restart_attempt: Option<u32>,
+}
+
+pub fn emit_lifecycle(event: &str, payload: BrowserLifecycleEvent) {
+    emit(event, payload);
+}
+
+pub fn emit<P: Serialize + Clone>(event: &str, payload: P) {
+    let Ok(app) = app_handle() else { return };
+    if let Err(e) = app.emit(event, payload) {
+        warn!("failed to emit {event}: {e}");
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::{Client, Proxy, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::endpoint::is_local_host;
+use crate::error::RuntimeError;
+use crate::settings::{load_setting, save_setting};
+
+const HTTP_SETTINGS_KEY: &str = "http_settings";
+const USER_AGENT: &str = concat!("runtime/", env!("CARGO_PKG_VERSION"));
+
+/*
+** what a request talks to decides which client it goes through:
+** - DevTools: the /json endpoints of a browser we manage or attach to, local or remote
+** - Probe: scans and health checks of local ports, many at once, fail fast
+** - Remote: the internet (skill files)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpProfile {
+    DevTools,
+    Probe,
+    Remote,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ClientSettings {
+    pub connect_timeout_ms: u64,
+    pub timeout_ms: u64,
+    /*
+     ** extra attempts after a timeout, a refused connection or a 5xx/429;
+     ** attempt n waits n * retry_delay_ms first
+     */
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 2000,
+            timeout_ms: 10_000,
+            retries: 0,
+            retry_delay_ms: 250,
+        }
+    }
+}
+
+impl ClientSettings {
+    fn validate(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.connect_timeout_ms == 0 || self.timeout_ms == 0 {
+            return Err(RuntimeError::InvalidInput {
+                reason: format!("{name} timeouts must be greater than 0"),
+            });
+        }
+        Ok(())
+    }
+}
+
+/*
+** `proxy` is used for remote traffic, and for DevTools endpoints that are not
+** on this machine. without one, remote traffic follows the system proxy
+** (HTTP_PROXY, HTTPS_PROXY, NO_PROXY) and DevTools traffic goes direct.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpSettings {
+    pub devtools: ClientSettings,
+    pub probe: ClientSettings,
+    pub remote: ClientSettings,
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            devtools: ClientSettings {
+                connect_timeout_ms: 1000,
+                timeout_ms: 5000,
+                retries: 0,
+                retry_delay_ms: 250,
+            },
+            /*
+             ** a closed port refuses instantly; these only bound ports that swallow the connect
+             */
+            probe: ClientSettings {
+                connect_timeout_ms: 300,
+                timeout_ms: 1500,
+                retries: 0,
+                retry_delay_ms: 0,
+            },
+            remote: ClientSettings {
+                connect_timeout_ms: 5000,
+                timeout_ms: 30_000,
+                retries: 2,
+                retry_delay_ms: 500,
+            },
+            proxy: None,
+        }
+    }
+}
+
+impl HttpSettings {
+    fn profile(&self, profile: HttpProfile) -> &ClientSettings {
+        match profile {
+            HttpProfile::DevTools => &self.devtools,
+            HttpProfile::Probe => &self.probe,
+            HttpProfile::Remote => &self.remote,
+        }
+    }
+
+    /*
+     ** hands back the parsed proxy. "host:3128" parses as a URL with scheme
+     ** "host", so the scheme and host are checked too.
+     */
+    fn validate(&self) -> Result<Option<Url>, RuntimeError> {
+        self.devtools.validate("DevTools")?;
+        self.probe.validate("Probe")?;
+        self.remote.validate("Remote")?;
+        let Some(proxy) = &self.proxy else {
+            return Ok(None);
+        };
+        let invalid = |reason: String| RuntimeError::InvalidInput {
+            reason: format!("Invalid proxy '{proxy}': {reason}"),
+        };
+        let url = Url::parse(proxy).map_err(|e| invalid(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err(invalid(
+                "expected an http://, https:// or socks5:// URL".to_string(),
+            ));
+        }
+        if url.host_str().is_none() {
+            return Err(invalid("missing host".to_string()));
+        }
+        Ok(Some(url))
+    }
+}
+
+fn build_client(
+    profile: HttpProfile,
+    settings: &HttpSettings,
+    proxy: Option<&Url>,
+) -> reqwest::Result<Client> {
+    let timeouts = settings.profile(profile);
+    let builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_millis(timeouts.connect_timeout_ms))
+        .timeout(Duration::from_millis(timeouts.timeout_ms));
+
+    let builder = match (profile, proxy) {
+        (HttpProfile::Probe, _) | (HttpProfile::DevTools, None) => builder.no_proxy(),
+        (HttpProfile::DevTools, Some(proxy)) => {
+            let proxy = proxy.clone();
+            builder.proxy(Proxy::custom(move |url| {
+                if url.host_str().is_some_and(is_local_host) {
+                    None
+                } else {
+                    Some(proxy.clone())
+                }
+            }))
+        }
+        (HttpProfile::Remote, Some(proxy)) => builder.proxy(Proxy::all(proxy.clone())?),
+        (HttpProfile::Remote, None) => builder,
+    };
+    builder.build()
+}
+
+struct HttpClients {
+    settings: HttpSettings,
+    devtools: Client,
+    probe: Client,
+    remote: Client,
+}
+
+impl HttpClients {
+    fn new(settings: HttpSettings) -> Result<Self, RuntimeError> {
+        let proxy = settings.validate()?;
+        let build = |profile| {
+            build_client(profile, &settings, proxy.as_ref())
+                .map_err(|e| RuntimeError::internal(format!("Failed to create HTTP clients: {e}")))
+        };
+        Ok(Self {
+            devtools: build(HttpProfile::DevTools)?,
+            probe: build(HttpProfile::Probe)?,
+            remote: build(HttpProfile::Remote)?,
+            settings,
+        })
+    }
+
+    fn client(&self, profile: HttpProfile) -> &Client {
+        match profile {
+            HttpProfile::DevTools => &self.devtools,
+            HttpProfile::Probe => &self.probe,
+            HttpProfile::Remote => &self.remote,
+        }
+    }
+}
+
+/*
+** built on first use from the saved settings; an unusable saved proxy falls
+** back to the defaults rather than leaving the app without HTTP
+*/
+static CLIENTS: Lazy<RwLock<HttpClients>> = Lazy::new(|| {
+    let settings: HttpSettings = load_setting(HTTP_SETTINGS_KEY).unwrap_or_default();
+    let clients = HttpClients::new(settings).unwrap_or_else(|e| {
+        error!("ignoring saved HTTP settings: {e}");
+        HttpClients::new(HttpSettings::default()).expect("default HTTP clients")
+    });
+    RwLock::new(clients)
+});
+
+/*
+** clients share their connection pool across clones
+*/
+pub fn client(profile: HttpProfile) -> Client {
+    CLIENTS.read().unwrap().client(profile).clone()
+}
+
+/*
+** GET with the profile's retry policy
+*/
+pub async fn get(profile: HttpProfile, url: &str) -> reqwest::Result<Response> {
+    let (client, settings) = {
+        let clients = CLIENTS.read().unwrap();
+        (
+            clients.client(profile).clone(),
+            clients.settings.profile(profile).clone(),
+        )
+    };
+
+    let mut attempt = 0;
+    loop {
+        let result = client.get(url).send().await;
+        let retryable = match &result {
+            Ok(resp) => {
+                resp.status().is_server_error() || resp.status() == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+        if !retryable || attempt >= settings.retries {
+            return result;
+        }
+        attempt += 1;
+        debug!("retrying {url} (attempt {attempt}/{})", settings.retries);
+        tokio::time::sleep(Duration::from_millis(
+            settings.retry_delay_ms * u64::from(attempt),
+        ))
+        .await;
+    }
+}
+
+pub fn http_settings() -> HttpSettings {
+    CLIENTS.read().unwrap().settings.clone()
+}
+
+/*
+** rebuilds every client; requests already in flight finish on the old ones
+*/
+pub fn save_http_settings(settings: &HttpSettings) -> Result<(), RuntimeError> {
+    let clients = HttpClients::new(settings.clone())?;
+    save_setting(HTTP_SETTINGS_KEY, settings)?;
+    *CLIENTS.write().unwrap() = clients;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_proxy(proxy: &str) -> HttpSettings {
+        HttpSettings {
+            proxy: Some(proxy.to_string()),
+            ..HttpSettings::default()
+        }
+    }
+
+    #[test]
+    fn proxy_must_be_a_url() {
+        for proxy in [
+            "host:3128",
+            "proxy.local",
+            "ftp://proxy.local:21",
+            "http://",
+        ] {
+            assert!(
+                matches!(
+                    with_proxy(proxy).validate(),
+                    Err(RuntimeError::InvalidInput { .. })
+                ),
+                "{proxy} should be rejected"
+            );
+        }
+        let url = with_proxy("socks5://10.0.0.2:1080").validate().unwrap();
+        assert_eq!(url.unwrap().port(), Some(1080));
+        assert!(HttpClients::new(with_proxy("http://10.0.0.2:3128")).is_ok());
+    }
+}
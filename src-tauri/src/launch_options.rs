@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::RuntimeError;
+
+/*
+** named starting points for LaunchOptions. "interactive" is the headed
+** browser the app has always opened; "headless" is for machines without a display.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchPreset {
+    #[default]
+    Interactive,
+    Headless,
+}
+
+/*
+** the interactive preset is exactly these; the others build on them
+*/
+const BASE_ARGS: &[&str] = &[
+    "--no-first-run",
+    "--no-default-browser-check",
+    "--disable-background-timer-throttling",
+    "--disable-backgrounding-occluded-windows",
+    "--disable-renderer-backgrounding",
+    "--enable-automation",
+];
+
+const HEADLESS_ARGS: &[&str] = &["--hide-scrollbars", "--mute-audio"];
+const HEADLESS_WINDOW: WindowSize = WindowSize {
+    width: 1920,
+    height: 1080,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+const DEFAULT_STARTER_PAGE: &str = "https://www.google.com";
+
+/*
+** everything about a launch that is not transport or profile. unset fields
+** fall back to the preset.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LaunchOptions {
+    pub preset: LaunchPreset,
+    pub headless: Option<bool>,
+    pub window_size: Option<WindowSize>,
+    pub window_position: Option<WindowPosition>,
+    pub extra_args: Vec<String>,
+    pub extensions: Vec<String>, // unpacked extension directories
+    pub proxy: Option<String>,   // e.g. "http://proxy:3128" or "socks5://127.0.0.1:1080"
+    pub locale: Option<String>,  // e.g. "en-US"
+    pub env: HashMap<String, String>,
+    pub starter_page: Option<String>, // opened once the browser is up; null for none
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            preset: LaunchPreset::default(),
+            headless: None,
+            window_size: None,
+            window_position: None,
+            extra_args: Vec::new(),
+            extensions: Vec::new(),
+            proxy: None,
+            locale: None,
+            env: HashMap::new(),
+            starter_page: Some(DEFAULT_STARTER_PAGE.to_string()),
+        }
+    }
+}
+
+impl LaunchOptions {
+    pub fn headless(&self) -> bool {
+        self.headless
+            .unwrap_or(self.preset == LaunchPreset::Headless)
+    }
+
+    pub fn validate(&self) -> Result<(), RuntimeError> {
+        let invalid = |reason: String| Err(RuntimeError::InvalidInput { reason });
+        if let Some(size) = self.window_size {
+            if size.width == 0 || size.height == 0 {
+                return invalid("Window size must be at least 1x1".to_string());
+            }
+        }
+        if let Some(extension) = self
+            .extensions
+            .iter()
+            .find(|dir| !std::path::Path::new(dir).is_dir())
+        {
+            return invalid(format!("Extension directory not found: {extension}"));
+        }
+        if self
+            .env
+            .keys()
+            .any(|key| key.is_empty() || key.contains('='))
+        {
+            return invalid(
+                "Environment variable names must be non-empty and contain no '='".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /*
+     ** browser flags for these options; transport and profile flags are added by the launcher
+     */
+    pub fn browser_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = BASE_ARGS.iter().map(|a| a.to_string()).collect();
+
+        let headless = self.headless();
+        if headless {
+            args.push("--headless=new".to_string());
+            args.extend(HEADLESS_ARGS.iter().map(|a| a.to_string()));
+        }
+
+        let window_size = self.window_size.or(headless.then_some(HEADLESS_WINDOW));
+        if let Some(size) = window_size {
+            args.push(format!("--window-size={},{}", size.width, size.height));
+        }
+        if let Some(position) = self.window_position {
+            args.push(format!("--window-position={},{}", position.x, position.y));
+        }
+
+        if !self.extensions.is_empty() {
+            args.push(format!("--load-extension={}", self.extensions.join(",")));
+        }
+        if let Some(proxy) = &self.proxy {
+            args.push(format!("--proxy-server={proxy}"));
+        }
+        if let Some(locale) = &self.locale {
+            args.push(format!("--lang={locale}"));
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    /*
+     ** --lang is ignored on Linux, where the UI language comes from the environment
+     */
+    pub fn browser_env(&self) -> HashMap<String, String> {
+        let mut env = self.env.clone();
+        if let Some(locale) = &self.locale {
+            env.entry("LANGUAGE".to_string())
+                .or_insert_with(|| locale.replace('-', "_"));
+        }
+        env
+    }
+}
@@ -1,20 +1,61 @@
 mod app_note;
 mod apps;
+mod artifacts;
+mod browser_logs;
 mod browser_manager;
+mod browser_version;
+mod cdp;
 mod commands;
 mod config;
+mod custom_browsers;
+#[cfg(target_os = "linux")]
+mod discovery;
+mod documents;
+mod downloads;
+mod endpoint;
+mod error;
+mod events;
+mod http;
+mod launch_options;
+mod logging;
 mod network;
+mod paths;
+#[cfg(unix)]
+mod pipe;
 mod platform;
+mod port_policy;
+mod printing;
+mod profiles;
+mod readability;
+mod readiness;
+mod registry;
+mod scanner;
+mod screenshots;
+mod settings;
 mod sketchs;
 mod sketchs_browser;
 mod skills;
+mod targets;
+#[cfg(test)]
+mod test;
 mod utils;
+mod watchdog;
 
 use commands::{
-    call_app, debug_browser_connection, disconnect_from_browser, fetch_available_browsers,
-    force_close_browser, launch_browser, load_skills, scan_for_existing_browsers,
-    validate_connection, validate_ws_endpoint,
+    activate_target, add_custom_browser, add_remote_endpoint, attach_to_target, call_app,
+    capture_screenshot, clone_browser_profile, close_browser_instance, close_target,
+    create_browser_profile, debug_browser_connection, delete_browser_profile,
+    disconnect_from_browser, enable_downloads, extract_document_text, extract_page_markdown,
+    fetch_available_browsers, force_close_browser, get_app_logs, get_browser_logs,
+    get_browser_version, get_http_settings, get_log_level, get_port_policy, get_restart_policy,
+    launch_browser, list_browser_instances, list_browser_profiles, list_custom_browsers,
+    list_downloads, list_session_artifacts, list_targets, load_skills, open_artifact, open_target,
+    print_to_pdf, reload_target, remove_custom_browser, rename_browser_profile,
+    scan_for_devtools_endpoints, scan_for_existing_browsers, select_browser_instance,
+    send_cdp_command, set_http_settings, set_log_level, set_port_policy, set_restart_policy,
+    update_custom_browser, validate_connection, validate_ws_endpoint, wait_for_download,
 };
+use tauri::RunEvent;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -22,18 +63,72 @@ pub fn run() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .setup(|app| {
+            paths::init(app.handle())?;
+            events::init(app.handle());
+            logging::init(app.handle())?;
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             fetch_available_browsers,
+            list_custom_browsers,
+            add_custom_browser,
+            update_custom_browser,
+            remove_custom_browser,
             launch_browser,
+            list_browser_instances,
+            select_browser_instance,
+            close_browser_instance,
             disconnect_from_browser,
             force_close_browser,
             validate_connection,
             validate_ws_endpoint,
             scan_for_existing_browsers,
+            scan_for_devtools_endpoints,
+            add_remote_endpoint,
             debug_browser_connection,
+            get_browser_logs,
+            get_app_logs,
+            get_log_level,
+            set_log_level,
+            list_browser_profiles,
+            create_browser_profile,
+            clone_browser_profile,
+            rename_browser_profile,
+            delete_browser_profile,
+            send_cdp_command,
+            attach_to_target,
+            get_browser_version,
+            list_targets,
+            activate_target,
+            close_target,
+            reload_target,
+            open_target,
+            capture_screenshot,
+            print_to_pdf,
+            list_session_artifacts,
+            open_artifact,
+            enable_downloads,
+            list_downloads,
+            wait_for_download,
+            extract_document_text,
+            extract_page_markdown,
+            get_port_policy,
+            set_port_policy,
+            get_http_settings,
+            set_http_settings,
+            get_restart_policy,
+            set_restart_policy,
             load_skills,
             call_app
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| match event {
+            RunEvent::ExitRequested { .. } => {
+                tauri::async_runtime::block_on(browser_manager::shutdown_all_launched());
+            }
+            RunEvent::Exit => logging::shutdown(),
+            _ => {}
+        });
 }
@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder as RollingBuilder, Rotation};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt as tracing_fmt, reload, EnvFilter, Layer, Registry};
+
+use crate::error::RuntimeError;
+use crate::settings::{load_setting, save_setting};
+use crate::utils::now_ms;
+
+const LOG_LEVEL_KEY: &str = "log_level";
+const LOG_LEVEL_ENV: &str = "RUNTIME_LOG";
+const LOG_FILE_PREFIX: &str = "runtime";
+const LOG_FILE_KEEP: usize = 7;
+const RECENT_CAPACITY: usize = 5000;
+const DEFAULT_QUERY_LIMIT: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+
+    /*
+     ** the level applies to our own crate; dependencies (hyper, tungstenite,
+     ** tauri) stay at warn so debug logs remain readable
+     */
+    fn filter(&self) -> EnvFilter {
+        EnvFilter::new(format!("warn,runtime_lib={}", self.as_str()))
+    }
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            Level::TRACE => LogLevel::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/*
+** one event as the log viewer sees it. `fields` merges the event's own
+** fields with those of its enclosing spans (innermost wins), so a record
+** logged anywhere under a command or instance span carries its instance_id.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LogRecord {
+    pub timestamp_ms: u64,
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+    pub spans: Vec<String>,
+    pub fields: Map<String, Value>,
+}
+
+/*
+** every filter is optional; `level` keeps records at least that severe and
+** `limit` keeps the most recent ones
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LogQuery {
+    pub level: Option<LogLevel>,
+    pub target: Option<String>,
+    pub span: Option<String>,
+    pub instance_id: Option<String>,
+    pub contains: Option<String>,
+    pub since_ms: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    fn matches(&self, record: &LogRecord) -> bool {
+        if self.level.is_some_and(|level| record.level > level) {
+            return false;
+        }
+        if self
+            .target
+            .as_deref()
+            .is_some_and(|target| !record.target.starts_with(target))
+        {
+            return false;
+        }
+        if self
+            .span
+            .as_deref()
+            .is_some_and(|span| !record.spans.iter().any(|name| name == span))
+        {
+            return false;
+        }
+        if let Some(instance_id) = self.instance_id.as_deref() {
+            if record.fields.get("instance_id").and_then(Value::as_str) != Some(instance_id) {
+                return false;
+            }
+        }
+        if let Some(needle) = self.contains.as_deref() {
+            let needle = needle.to_lowercase();
+            if !record.message.to_lowercase().contains(&needle) {
+                return false;
+            }
+        }
+        self.since_ms
+            .is_none_or(|since_ms| record.timestamp_ms >= since_ms)
+    }
+}
+
+static RECENT_LOGS: Lazy<Mutex<VecDeque<LogRecord>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)));
+static LEVEL_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+static CURRENT_LEVEL: Mutex<LogLevel> = Mutex::new(LogLevel::Info);
+
+/*
+** the file writer flushes from a background thread; its guard must live
+** until the app exits or the last records are lost
+*/
+static FILE_GUARD: Mutex<Option<WorkerGuard>> = Mutex::new(None);
+
+/*
+** install the global subscriber: daily-rotated JSON files in the app log
+** dir, human-readable stderr, and the in-memory buffer behind query_logs.
+** the level comes from the saved setting, then RUNTIME_LOG, then info.
+*/
+pub fn init(app: &AppHandle) -> Result<(), RuntimeError> {
+    let level = load_setting::<LogLevel>(LOG_LEVEL_KEY)
+        .or_else(|| {
+            std::env::var(LOG_LEVEL_ENV)
+                .ok()
+                .and_then(|value| LogLevel::parse(&value))
+        })
+        .unwrap_or(LogLevel::Info);
+    *CURRENT_LEVEL.lock().unwrap() = level;
+
+    let (filter, handle) = reload::Layer::new(level.filter());
+
+    let log_dir = app
+        .path()
+        .app_log_dir()
+        .map_err(|e| RuntimeError::internal(format!("Failed to resolve app log dir: {e}")))?;
+    /*
+     ** nothing is listening yet; a broken log dir is reported once the
+     ** subscriber is up, so it lands in the in-memory buffer too
+     */
+    let mut file_error = None;
+    let file_layer = match RollingBuilder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(LOG_FILE_KEEP)
+        .build(&log_dir)
+    {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            *FILE_GUARD.lock().unwrap() = Some(guard);
+            Some(
+                tracing_fmt::layer()
+                    .json()
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_writer(writer),
+            )
+        }
+        Err(e) => {
+            file_error = Some(e);
+            None
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(file_layer)
+        .with(tracing_fmt::layer().with_writer(std::io::stderr))
+        .with(RecentLogsLayer)
+        .try_init()
+        .map_err(|e| {
+            RuntimeError::internal(format!("Failed to install the log subscriber: {e}"))
+        })?;
+    let _ = LEVEL_HANDLE.set(handle);
+
+    if let Some(e) = file_error {
+        tracing::warn!(log_dir = %log_dir.display(), "logs will not be written to disk: {e}");
+    }
+
+    tracing::info!(level = %level, log_dir = %log_dir.display(), "logging initialised");
+    Ok(())
+}
+
+/*
+** flush pending file writes; called once the event loop is exiting
+*/
+pub fn shutdown() {
+    FILE_GUARD.lock().unwrap().take();
+}
+
+pub fn log_level() -> LogLevel {
+    *CURRENT_LEVEL.lock().unwrap()
+}
+
+/*
+** takes effect immediately and is remembered for the next start
+*/
+pub fn set_log_level(level: LogLevel) -> Result<(), RuntimeError> {
+    let handle = LEVEL_HANDLE
+        .get()
+        .ok_or_else(|| RuntimeError::internal("Logging is not initialised yet"))?;
+    handle
+        .reload(level.filter())
+        .map_err(|e| RuntimeError::internal(format!("Failed to change log level: {e}")))?;
+    *CURRENT_LEVEL.lock().unwrap() = level;
+    tracing::info!(level = %level, "log level changed");
+    save_setting(LOG_LEVEL_KEY, &level)
+}
+
+/*
+** oldest first, like get_browser_logs
+*/
+pub fn query_logs(query: &LogQuery) -> Vec<LogRecord> {
+    let limit = query.limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+    let recent = RECENT_LOGS.lock().unwrap();
+    let mut records: Vec<LogRecord> = recent
+        .iter()
+        .rev()
+        .filter(|record| query.matches(record))
+        .take(limit)
+        .cloned()
+        .collect();
+    records.reverse();
+    records
+}
+
+fn remember(record: LogRecord) {
+    let mut recent = RECENT_LOGS.lock().unwrap();
+    if recent.len() == RECENT_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(record);
+}
+
+/*
+** span fields as recorded so far, kept in the span's extensions
+*/
+struct SpanFields(Map<String, Value>);
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), Value::from(format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+}
+
+struct RecentLogsLayer;
+
+impl<S> Layer<S> for RecentLogsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let message = match fields.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+
+        let mut spans = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                spans.push(span.name().to_string());
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    for (key, value) in span_fields {
+                        fields.entry(key.clone()).or_insert_with(|| value.clone());
+                    }
+                }
+            }
+        }
+        spans.reverse();
+
+        let metadata = event.metadata();
+        remember(LogRecord {
+            timestamp_ms: now_ms(),
+            level: metadata.level().into(),
+            target: metadata.target().to_string(),
+            message,
+            spans,
+            fields,
+        });
+    }
+}
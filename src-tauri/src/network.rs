@@ -1,153 +1,88 @@
-use reqwest::Client;
 use std::net::TcpListener;
 use std::time::Duration;
 
-pub fn find_free_port(start_port: u16) -> Option<u16> {
-    let mut port = start_port;
-    let mut tries = 0;
+use crate::browser_version::BrowserVersion;
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+use crate::http::{self, HttpProfile};
+use crate::port_policy::PortRange;
+use crate::sketchs::BrowserTarget;
+use tracing::debug;
 
-    while tries < 100 {
-        port += 1;
-        tries += 1;
-        if TcpListener::bind(format!("127.0.0.1:{port}")).is_ok() {
-            return Some(port);
-        }
-    }
-
-    /*
-     ** final fallback – sometimes this gives a random free port
-     */
-    if let Ok(listener) = TcpListener::bind(format!("127.0.0.1:{start_port}")) {
-        if let Ok(addr) = listener.local_addr() {
-            return Some(addr.port());
-        }
-    }
-
-    None
-}
-
-pub async fn scan_for_existing_browser_instances(target_browser_type: &str) -> Option<String> {
-    let ports_to_scan = [
-        9222, 9223, 9224, 9225, 9226, 9227, 9228, 9229, 9230, 9231, 9232,
-    ];
-
-    for port in ports_to_scan {
-        match get_browser_info(&port.to_string()).await {
-            Ok((browser_string, user_agent)) => {
-                let detected = determine_browser_type(&browser_string, &user_agent);
-
-                if detected == target_browser_type {
-                    match get_browser_websocket_url(port, 3, 500).await {
-                        Ok(ws_url) => {
-                            println!("Found existing {detected} instance on port {port}: {ws_url}");
-                            return Some(ws_url);
-                        }
-                        Err(_) => { /* keep scanning */ }
-                    }
-                }
-            }
-            Err(_) => { /* keep scanning */ }
-        }
-    }
-    None
+pub fn is_port_free(port: u16) -> bool {
+    TcpListener::bind(format!("127.0.0.1:{port}")).is_ok()
 }
 
-pub fn extract_port_from_ws_url(ws_endpoint: &str) -> Result<String, String> {
-    let url = ws_endpoint
-        .replace("ws://", "http://")
-        .replace("wss://", "https://");
-    let parts: Vec<&str> = url.split('/').collect();
-    if parts.len() < 3 {
-        return Err("Invalid WebSocket URL format".to_string());
-    }
-
-    let host_port = parts[2];
-    let port = host_port.split(':').nth(1).unwrap_or("9222");
-    Ok(port.to_string())
+pub fn find_free_port(range: PortRange) -> Option<u16> {
+    range.ports().find(|port| is_port_free(*port))
 }
 
-pub async fn get_browser_info(port: &str) -> Result<(String, String), String> {
-    let version_url = format!("http://127.0.0.1:{port}/json/version");
-
-    let client = Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
+pub async fn get_browser_info(endpoint: &CdpEndpoint) -> Result<BrowserVersion, RuntimeError> {
+    let version_url = endpoint.http_url("/json/version");
 
-    let response = client
-        .get(&version_url)
-        .send()
+    let response = http::get(HttpProfile::DevTools, &version_url)
         .await
-        .map_err(|e| format!("Failed to fetch version: {e}"))?;
+        .map_err(|e| RuntimeError::unreachable(&version_url, e))?;
 
     if !response.status().is_success() {
-        return Err(format!("HTTP {}", response.status()));
+        return Err(RuntimeError::unreachable(
+            &version_url,
+            format!("HTTP {}", response.status()),
+        ));
     }
 
     let version_data: serde_json::Value = response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse JSON: {e}"))?;
-
-    let browser_string = version_data["Browser"].as_str().unwrap_or("").to_string();
-    let user_agent = version_data["User-Agent"]
-        .as_str()
-        .unwrap_or("")
-        .to_string();
+        .map_err(|e| RuntimeError::unreachable(&version_url, format!("invalid JSON: {e}")))?;
 
-    println!("Browser info: {browser_string}");
+    let version = BrowserVersion::from_json_version(&version_data)
+        .map_err(|e| RuntimeError::unreachable(&version_url, e))?;
+    debug!("Browser info: {}", version.product);
 
-    Ok((browser_string, user_agent))
-}
-
-pub fn determine_browser_type(browser_string: &str, user_agent: &str) -> String {
-    if browser_string.contains("Edg/") || user_agent.contains("Edg/") {
-        "edge".to_string()
-    } else if browser_string.contains("Arc/") || user_agent.contains("Arc/") {
-        "arc".to_string()
-    } else if browser_string.contains("Chrome/") || user_agent.contains("Chrome/") {
-        "chrome".to_string()
-    } else {
-        "unknown".to_string()
-    }
+    Ok(version)
 }
 
+/*
+** the browser target's ws url, reachable from here (see CdpEndpoint::with_ws_url)
+*/
 pub async fn get_browser_websocket_url(
-    port: u16,
+    endpoint: &CdpEndpoint,
     max_retries: u32,
     delay_ms: u64,
-) -> Result<String, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let version_url = format!("http://127.0.0.1:{port}/json/version");
-    println!("checking for DevTools endpoint at: {version_url}");
+) -> Result<String, RuntimeError> {
+    let version_url = endpoint.http_url("/json/version");
+    debug!("checking for DevTools endpoint at: {version_url}");
 
     for attempt in 1..=max_retries {
-        match client.get(&version_url).send().await {
+        match http::get(HttpProfile::DevTools, &version_url).await {
             Ok(resp) if resp.status().is_success() => {
-                let json_data = resp
-                    .json::<serde_json::Value>()
-                    .await
-                    .map_err(|e| format!("failed to parse JSON: {e}"))?;
-
-                if let Some(ws_url) = json_data["webSocketDebuggerUrl"].as_str() {
-                    println!("DevTools WebSocket URL: {ws_url}");
-                    return Ok(ws_url.to_string());
+                let json_data = resp.json::<serde_json::Value>().await.map_err(|e| {
+                    RuntimeError::unreachable(&version_url, format!("invalid JSON: {e}"))
+                })?;
+
+                if let Some(reported) = json_data["webSocketDebuggerUrl"].as_str() {
+                    let ws_url = endpoint
+                        .with_ws_url(reported)?
+                        .ws_url()
+                        .unwrap_or_else(|| reported.to_string());
+                    debug!("DevTools WebSocket URL: {ws_url}");
+                    return Ok(ws_url);
                 } else {
-                    return Err("webSocketDebuggerUrl not found in browser response".to_string());
+                    return Err(RuntimeError::unreachable(
+                        &version_url,
+                        "webSocketDebuggerUrl not found in browser response",
+                    ));
                 }
             }
             Ok(_) => {
                 /*
                  ** continue with the next attempt
                  */
-                println!("attempt {attempt}/{max_retries}: non-success response");
+                debug!("attempt {attempt}/{max_retries}: non-success response");
             }
             Err(e) => {
-                println!("attempt {attempt}/{max_retries}: connection failed: {e}");
+                debug!("attempt {attempt}/{max_retries}: connection failed: {e}");
             }
         }
 
@@ -156,43 +91,137 @@ pub async fn get_browser_websocket_url(
         }
     }
 
-    Err(format!(
-        "Failed to connect to browser debugging interface after {max_retries} attempts. The browser may not have started with debugging enabled."
+    Err(RuntimeError::unreachable(
+        &version_url,
+        format!("no answer after {max_retries} attempts. The browser may not have started with debugging enabled."),
     ))
 }
 
-pub async fn create_new_page(port: u16, url: Option<&str>) -> Result<String, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {e}"))?;
+/*
+** the whole query is the page url, so `&` and `#` in it must be escaped.
+** spaces become %20: the browser does not read `+` as a space.
+*/
+fn new_page_query(url: &str) -> String {
+    url::form_urlencoded::byte_serialize(url.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
 
-    let target_url = url.unwrap_or("");
-    let new_page_url = format!("http://127.0.0.1:{port}/json/new?{target_url}");
+/*
+** Chrome 111 and later refuse GET on /json/new
+*/
+pub async fn create_new_page(
+    endpoint: &CdpEndpoint,
+    url: Option<&str>,
+) -> Result<String, RuntimeError> {
+    let query = new_page_query(url.unwrap_or(""));
+    let new_page_url = endpoint.http_url(&format!("/json/new?{query}"));
 
-    println!("creating new page: {new_page_url}");
+    debug!("creating new page: {new_page_url}");
 
-    match client.get(&new_page_url).send().await {
+    match http::client(HttpProfile::DevTools)
+        .put(&new_page_url)
+        .send()
+        .await
+    {
         Ok(resp) if resp.status().is_success() => {
-            let json_data = resp
-                .json::<serde_json::Value>()
-                .await
-                .map_err(|e| format!("Failed to parse new page response: {e}"))?;
+            let json_data = resp.json::<serde_json::Value>().await.map_err(|e| {
+                RuntimeError::internal(format!("Failed to parse new page response: {e}"))
+            })?;
 
             if let Some(page_id) = json_data["id"].as_str() {
-                println!("new page created with ID: {page_id}");
+                debug!("new page created with ID: {page_id}");
                 Ok(page_id.to_string())
             } else {
-                Err("No page ID returned from new page creation".to_string())
+                Err(RuntimeError::internal(
+                    "No page ID returned from new page creation",
+                ))
             }
         }
         Ok(resp) => {
             let status = resp.status();
             let body = resp.text().await.unwrap_or_default();
-            Err(format!(
+            Err(RuntimeError::internal(format!(
                 "Failed to create new page. Status: {status}, Body: {body}"
-            ))
+            )))
         }
-        Err(e) => Err(format!("Failed to create new page: {e}")),
+        Err(e) => Err(RuntimeError::unreachable(&new_page_url, e)),
+    }
+}
+
+/*
+** /json/list has no attached flag; the browser leaves out
+** webSocketDebuggerUrl while a client is attached to that target
+*/
+pub async fn list_targets(endpoint: &CdpEndpoint) -> Result<Vec<BrowserTarget>, RuntimeError> {
+    let list_url = endpoint.http_url("/json/list");
+    let targets: Vec<serde_json::Value> = http::get(HttpProfile::DevTools, &list_url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| RuntimeError::unreachable(&list_url, e))?
+        .json()
+        .await
+        .map_err(|e| RuntimeError::internal(format!("Failed to parse target list: {e}")))?;
+
+    Ok(targets
+        .iter()
+        .filter_map(|target| {
+            Some(BrowserTarget {
+                target_id: target["id"].as_str()?.to_string(),
+                target_type: target["type"].as_str().unwrap_or_default().to_string(),
+                title: target["title"].as_str().unwrap_or_default().to_string(),
+                url: target["url"].as_str().unwrap_or_default().to_string(),
+                attached: target.get("webSocketDebuggerUrl").is_none(),
+            })
+        })
+        .collect())
+}
+
+/*
+** /json/activate/<id> and /json/close/<id>; both answer with plain text
+*/
+async fn target_request(
+    endpoint: &CdpEndpoint,
+    action: &str,
+    target_id: &str,
+) -> Result<(), RuntimeError> {
+    let url = endpoint.http_url(&format!("/json/{action}/{target_id}"));
+    let resp = http::get(HttpProfile::DevTools, &url)
+        .await
+        .map_err(|e| RuntimeError::unreachable(&url, e))?;
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    Err(RuntimeError::internal(format!(
+        "Failed to {action} target {target_id}. Status: {status}, Body: {}",
+        body.trim()
+    )))
+}
+
+pub async fn activate_target(endpoint: &CdpEndpoint, target_id: &str) -> Result<(), RuntimeError> {
+    target_request(endpoint, "activate", target_id).await
+}
+
+pub async fn close_target(endpoint: &CdpEndpoint, target_id: &str) -> Result<(), RuntimeError> {
+    target_request(endpoint, "close", target_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_page_urls_are_escaped_whole() {
+        assert_eq!(
+            new_page_query("https://example.com/a b?q=1&r=2#top"),
+            "https%3A%2F%2Fexample.com%2Fa%20b%3Fq%3D1%26r%3D2%23top"
+        );
+        assert_eq!(new_page_query("about:blank"), "about%3Ablank");
+        assert_eq!(
+            new_page_query("https://x.test/?a=1+2"),
+            "https%3A%2F%2Fx.test%2F%3Fa%3D1%2B2"
+        );
     }
 }
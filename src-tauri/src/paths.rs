@@ -0,0 +1,37 @@
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::OnceCell;
+use tauri::{AppHandle, Manager};
+
+use crate::error::RuntimeError;
+
+static APP_DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/*
+** resolve the app data dir once at startup so non-command code can use it
+*/
+pub fn init(app: &AppHandle) -> Result<(), RuntimeError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| RuntimeError::internal(format!("Failed to resolve app data dir: {e}")))?;
+    std::fs::create_dir_all(&dir).map_err(|e| RuntimeError::storage(&dir, e))?;
+    let _ = APP_DATA_DIR.set(dir);
+    Ok(())
+}
+
+pub fn app_data_dir() -> Result<&'static Path, RuntimeError> {
+    APP_DATA_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| RuntimeError::internal("App data dir is not initialised yet"))
+}
+
+/*
+** `<app data>/<name>`, created on first use
+*/
+pub fn app_data_subdir(name: &str) -> Result<PathBuf, RuntimeError> {
+    let dir = app_data_dir()?.join(name);
+    std::fs::create_dir_all(&dir).map_err(|e| RuntimeError::storage(&dir, e))?;
+    Ok(dir)
+}
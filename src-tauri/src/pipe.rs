@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+
+/*
+** our ends of the two pipes handed to a browser started with
+** --remote-debugging-pipe: it reads commands on fd 3 and answers on fd 4.
+*/
+#[derive(Debug)]
+pub struct DebugPipe {
+    pub writer: File,
+    pub reader: File,
+}
+
+/*
+** close-on-exec from the start: a Command spawned on another thread in
+** between must not inherit our ends, or EOF never arrives when the browser dies
+*/
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cloexec_pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/*
+** macOS has no pipe2; a spawn racing the fcntl calls can still leak these
+*/
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn cloexec_pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    for fd in [&read, &write] {
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((read, write))
+}
+
+pub fn spawn_with_debug_pipe(command: &mut Command) -> io::Result<(Child, DebugPipe)> {
+    let (child_read, parent_write) = cloexec_pipe()?;
+    let (parent_read, child_write) = cloexec_pipe()?;
+
+    let read_fd = child_read.as_raw_fd();
+    let write_fd = child_write.as_raw_fd();
+
+    /*
+     ** runs in the forked child right before exec. both ends are first moved
+     ** above fd 4 so the dup2 calls below can never clobber each other.
+     */
+    unsafe {
+        command.pre_exec(move || {
+            let read_fd = libc::fcntl(read_fd, libc::F_DUPFD, 5);
+            let write_fd = libc::fcntl(write_fd, libc::F_DUPFD, 5);
+            if read_fd < 0
+                || write_fd < 0
+                || libc::dup2(read_fd, 3) < 0
+                || libc::dup2(write_fd, 4) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            libc::close(read_fd);
+            libc::close(write_fd);
+            Ok(())
+        });
+    }
+
+    let child = command.spawn()?;
+
+    /*
+     ** the browser owns these now; keeping them open would hide EOF from both sides
+     */
+    drop(child_read);
+    drop(child_write);
+
+    Ok((
+        child,
+        DebugPipe {
+            writer: File::from(parent_write),
+            reader: File::from(parent_read),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_ends_are_close_on_exec() {
+        let (read, write) = cloexec_pipe().unwrap();
+        for fd in [&read, &write] {
+            let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+            assert!(flags >= 0 && flags & libc::FD_CLOEXEC != 0);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use std::path::PathBuf;
+
 use crate::sketchs::BrowserConfig;
+#[cfg(not(target_os = "linux"))]
 use crate::utils::check_browser;
 
 #[cfg(target_os = "windows")]
@@ -102,45 +105,76 @@ pub fn detect_browsers() -> Vec<BrowserConfig> {
 
 #[cfg(target_os = "linux")]
 pub fn detect_browsers() -> Vec<BrowserConfig> {
-    let mut browsers: Vec<BrowserConfig> = Vec::new();
+    crate::discovery::discover_browsers()
+}
 
-    let paths = [
-        // common binaries could be symlinks
-        (
-            "/usr/bin/google-chrome-stable",
-            "chrome",
-            "Google Chrome Stable",
-        ),
-        ("/usr/bin/google-chrome", "chrome", "Google Chrome"),
-        ("/usr/bin/microsoft-edge", "edge", "Microsoft Edge"),
-        (
-            "/usr/bin/microsoft-edge-stable",
-            "edge",
-            "Microsoft Edge Stable",
-        ),
-        (
-            "/usr/bin/microsoft-edge-beta",
-            "edge",
-            "Microsoft Edge Beta",
-        ),
-        ("/usr/bin/microsoft-edge-dev", "edge", "Microsoft Edge Dev"),
-        ("/usr/bin/arc", "arc", "Arc"),
-        ("/usr/local/bin/arc", "arc", "Arc"),
-        ("/opt/arc/arc", "arc", "Arc"),
-    ];
+/*
+** where browsers keep their own default profiles; a DevToolsActivePort file in
+** one of them means that browser was started with remote debugging
+*/
+#[cfg(target_os = "windows")]
+pub fn default_user_data_dirs() -> Vec<PathBuf> {
+    let Ok(local_app_data) = std::env::var("LOCALAPPDATA") else {
+        return Vec::new();
+    };
+    let base = PathBuf::from(local_app_data);
+    [
+        "Google\\Chrome\\User Data",
+        "Google\\Chrome Beta\\User Data",
+        "Google\\Chrome SxS\\User Data",
+        "Chromium\\User Data",
+        "Microsoft\\Edge\\User Data",
+        "BraveSoftware\\Brave-Browser\\User Data",
+        "Vivaldi\\User Data",
+    ]
+    .iter()
+    .map(|dir| base.join(dir))
+    .collect()
+}
 
-    for (path_str, id, name) in paths.iter() {
-        if Path::new(path_str).exists() {
-            if !browsers.iter().any(|b| b.path == *path_str) {
-                println!("Found browser: {} at {}", name, path_str);
-                browsers.push(BrowserConfig {
-                    id: id.to_string(),
-                    name: name.to_string(),
-                    path: path_str.to_string(),
-                });
-            }
-        }
-    }
+#[cfg(target_os = "macos")]
+pub fn default_user_data_dirs() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("HOME") else {
+        return Vec::new();
+    };
+    let base = PathBuf::from(home).join("Library/Application Support");
+    [
+        "Google/Chrome",
+        "Google/Chrome Beta",
+        "Google/Chrome Canary",
+        "Chromium",
+        "Microsoft Edge",
+        "Arc/User Data",
+        "BraveSoftware/Brave-Browser",
+        "Vivaldi",
+    ]
+    .iter()
+    .map(|dir| base.join(dir))
+    .collect()
+}
 
-    browsers
+#[cfg(target_os = "linux")]
+pub fn default_user_data_dirs() -> Vec<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => return Vec::new(),
+        },
+    };
+    [
+        "google-chrome",
+        "google-chrome-beta",
+        "google-chrome-unstable",
+        "chromium",
+        "microsoft-edge",
+        "microsoft-edge-beta",
+        "microsoft-edge-dev",
+        "BraveSoftware/Brave-Browser",
+        "vivaldi",
+        "opera",
+    ]
+    .iter()
+    .map(|dir| config.join(dir))
+    .collect()
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::RuntimeError;
+use crate::settings::{load_setting, save_setting};
+
+const PORT_POLICY_KEY: &str = "port_policy";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn ports(&self) -> impl Iterator<Item = u16> {
+        self.start..=self.end
+    }
+}
+
+/*
+** which DevTools ports we launch on and look at. launch tries the browser's
+** preferred port, then the fallback range; scan covers all of them plus the
+** usual 9222.. range other tools use and any extra ranges.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PortPolicy {
+    pub preferred: HashMap<String, u16>,
+    pub fallback: PortRange,
+    pub auto_select: bool,
+    pub scan: PortRange,
+    pub extra_scan_ranges: Vec<PortRange>,
+}
+
+impl Default for PortPolicy {
+    fn default() -> Self {
+        Self {
+            preferred: HashMap::from([
+                ("chrome".to_string(), 9522),
+                ("edge".to_string(), 9523),
+                ("arc".to_string(), 9524),
+            ]),
+            fallback: PortRange {
+                start: 9525,
+                end: 9545,
+            },
+            auto_select: true,
+            scan: PortRange {
+                start: 9222,
+                end: 9232,
+            },
+            extra_scan_ranges: Vec::new(),
+        }
+    }
+}
+
+impl PortPolicy {
+    /*
+     ** browsers without their own entry share chrome's port
+     */
+    pub fn preferred_port(&self, browser_id: &str) -> u16 {
+        self.preferred
+            .get(browser_id)
+            .or_else(|| self.preferred.get("chrome"))
+            .copied()
+            .unwrap_or(self.fallback.start)
+    }
+
+    pub fn scan_ports(&self) -> Vec<u16> {
+        let mut ports: Vec<u16> = self.preferred.values().copied().collect();
+        ports.extend(self.fallback.ports());
+        ports.extend(self.scan.ports());
+        for range in &self.extra_scan_ranges {
+            ports.extend(range.ports());
+        }
+        ports.sort_unstable();
+        ports.dedup();
+        ports
+    }
+
+    fn validate(&self) -> Result<(), RuntimeError> {
+        let invalid = |reason: String| Err(RuntimeError::InvalidInput { reason });
+        let ranges = [("fallback", &self.fallback), ("scan", &self.scan)]
+            .into_iter()
+            .chain(self.extra_scan_ranges.iter().map(|r| ("extra scan", r)));
+        for (name, range) in ranges {
+            if range.start == 0 || range.start > range.end {
+                return invalid(format!(
+                    "Invalid {name} port range {}-{}",
+                    range.start, range.end
+                ));
+            }
+        }
+        if let Some((browser_id, _)) = self.preferred.iter().find(|(_, port)| **port == 0) {
+            return invalid(format!("Preferred port for {browser_id} must not be 0"));
+        }
+        Ok(())
+    }
+}
+
+pub fn port_policy() -> PortPolicy {
+    load_setting(PORT_POLICY_KEY).unwrap_or_default()
+}
+
+pub fn save_port_policy(policy: &PortPolicy) -> Result<(), RuntimeError> {
+    policy.validate()?;
+    save_setting(PORT_POLICY_KEY, policy)
+}
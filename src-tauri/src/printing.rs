@@ -0,0 +1,334 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::artifacts::{self, Artifact, ArtifactKind, ArtifactSource};
+use crate::browser_manager::managed_cdp_session;
+use crate::cdp::{GetTargetInfo, PrintToPdf};
+use crate::error::RuntimeError;
+
+/*
+** an empty template hides that part; without it the browser prints its
+** default date and title header (or url and page number footer)
+*/
+const EMPTY_TEMPLATE: &str = "<span></span>";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperFormat {
+    #[default]
+    Letter,
+    Legal,
+    Tabloid,
+    Ledger,
+    A3,
+    A4,
+    A5,
+    A6,
+}
+
+impl PaperFormat {
+    /*
+     ** (width, height) in inches, portrait
+     */
+    fn size(&self) -> (f64, f64) {
+        match self {
+            PaperFormat::Letter => (8.5, 11.0),
+            PaperFormat::Legal => (8.5, 14.0),
+            PaperFormat::Tabloid => (11.0, 17.0),
+            PaperFormat::Ledger => (17.0, 11.0),
+            PaperFormat::A3 => (11.7, 16.54),
+            PaperFormat::A4 => (8.27, 11.7),
+            PaperFormat::A5 => (5.83, 8.27),
+            PaperFormat::A6 => (4.13, 5.83),
+        }
+    }
+}
+
+/*
+** inches; the default is the browser's own (about 1cm)
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct PageMargins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Default for PageMargins {
+    fn default() -> Self {
+        Self {
+            top: 0.4,
+            right: 0.4,
+            bottom: 0.4,
+            left: 0.4,
+        }
+    }
+}
+
+/*
+** `paper_width`/`paper_height` (inches) override `paper` when both are set.
+** header and footer templates are HTML; the browser fills elements with the
+** classes date, title, url, pageNumber and totalPages.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub title: Option<String>,
+    pub paper: PaperFormat,
+    pub paper_width: Option<f64>,
+    pub paper_height: Option<f64>,
+    pub landscape: bool,
+    pub margins: PageMargins,
+    pub print_background: bool,
+    pub scale: Option<f64>,
+    pub page_ranges: Option<String>,
+    pub header_template: Option<String>,
+    pub footer_template: Option<String>,
+    pub prefer_css_page_size: bool,
+}
+
+impl PdfOptions {
+    fn validate(&self) -> Result<(), RuntimeError> {
+        let invalid = |reason: &str| {
+            Err(RuntimeError::InvalidInput {
+                reason: reason.to_string(),
+            })
+        };
+        match (self.paper_width, self.paper_height) {
+            (Some(width), Some(height)) if !(width > 0.0 && height > 0.0) => {
+                return invalid("paper width and height must be greater than 0");
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return invalid("paper width and height must be given together");
+            }
+            _ => {}
+        }
+        let PageMargins {
+            top,
+            right,
+            bottom,
+            left,
+        } = self.margins;
+        if [top, right, bottom, left]
+            .iter()
+            .any(|margin| !(0.0..).contains(margin))
+        {
+            return invalid("margins must not be negative");
+        }
+        if self
+            .scale
+            .is_some_and(|scale| !(0.1..=2.0).contains(&scale))
+        {
+            return invalid("scale must be between 0.1 and 2");
+        }
+        Ok(())
+    }
+
+    fn command(&self) -> PrintToPdf {
+        let (paper_width, paper_height) = match (self.paper_width, self.paper_height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => self.paper.size(),
+        };
+        let display_header_footer =
+            self.header_template.is_some() || self.footer_template.is_some();
+        let template = |template: &Option<String>| {
+            display_header_footer.then(|| {
+                template
+                    .clone()
+                    .unwrap_or_else(|| EMPTY_TEMPLATE.to_string())
+            })
+        };
+        PrintToPdf {
+            landscape: self.landscape,
+            display_header_footer,
+            print_background: self.print_background,
+            scale: self.scale.unwrap_or(1.0),
+            paper_width,
+            paper_height,
+            margin_top: self.margins.top,
+            margin_bottom: self.margins.bottom,
+            margin_left: self.margins.left,
+            margin_right: self.margins.right,
+            page_ranges: self.page_ranges.clone(),
+            header_template: template(&self.header_template),
+            footer_template: template(&self.footer_template),
+            prefer_css_page_size: self.prefer_css_page_size,
+        }
+    }
+}
+
+/*
+** headed Chrome answers printToPDF with "not implemented"
+*/
+fn print_error(error: RuntimeError) -> RuntimeError {
+    match error {
+        RuntimeError::CdpCommandFailed { reason } if reason.contains("not implemented") => {
+            RuntimeError::Unsupported {
+                reason: "Printing to PDF needs a headless browser".to_string(),
+            }
+        }
+        error => error,
+    }
+}
+
+/*
+** print the target and keep the PDF as an artifact of `session_id`. it is
+** named after `options.title`, else the page title.
+*/
+pub async fn print_to_pdf(
+    instance_id: Option<&str>,
+    target_id: &str,
+    session_id: &str,
+    options: &PdfOptions,
+) -> Result<Artifact, RuntimeError> {
+    options.validate()?;
+    artifacts::validate_session_id(session_id)?;
+
+    let session = managed_cdp_session(instance_id).await?;
+    let page = session
+        .execute(&GetTargetInfo {
+            target_id: target_id.to_string(),
+        })
+        .await?
+        .target_info;
+    let target = session.attach_to_target(target_id).await?;
+    let printed = target.execute(&options.command()).await;
+    let _ = target.detach().await;
+    let printed = printed.map_err(print_error)?;
+
+    let bytes = BASE64
+        .decode(printed.data)
+        .map_err(|e| RuntimeError::internal(format!("Browser sent an invalid PDF: {e}")))?;
+    let title = options
+        .title
+        .clone()
+        .or_else(|| (!page.title.is_empty()).then_some(page.title));
+    artifacts::store(
+        session_id,
+        ArtifactKind::Pdf,
+        &bytes,
+        ArtifactSource {
+            title,
+            url: Some(page.url),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(options: &PdfOptions) -> String {
+        match options.validate() {
+            Err(RuntimeError::InvalidInput { reason }) => reason,
+            other => panic!("expected InvalidInput, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn paper_size_comes_from_the_format_unless_both_sides_are_given() {
+        let a4 = PdfOptions {
+            paper: PaperFormat::A4,
+            ..PdfOptions::default()
+        };
+        let command = a4.command();
+        assert_eq!((command.paper_width, command.paper_height), (8.27, 11.7));
+
+        let custom = PdfOptions {
+            paper_width: Some(4.0),
+            paper_height: Some(6.0),
+            ..a4
+        };
+        assert!(custom.validate().is_ok());
+        let command = custom.command();
+        assert_eq!((command.paper_width, command.paper_height), (4.0, 6.0));
+
+        for (width, height, reason) in [
+            (
+                Some(4.0),
+                None,
+                "paper width and height must be given together",
+            ),
+            (
+                None,
+                Some(6.0),
+                "paper width and height must be given together",
+            ),
+            (
+                Some(0.0),
+                Some(6.0),
+                "paper width and height must be greater than 0",
+            ),
+        ] {
+            let options = PdfOptions {
+                paper_width: width,
+                paper_height: height,
+                ..PdfOptions::default()
+            };
+            assert_eq!(rejection(&options), reason, "{width:?} x {height:?}");
+        }
+    }
+
+    #[test]
+    fn margins_must_not_be_negative() {
+        let zero = PdfOptions {
+            margins: PageMargins {
+                top: 0.0,
+                right: 0.0,
+                bottom: 0.0,
+                left: 0.0,
+            },
+            ..PdfOptions::default()
+        };
+        assert!(zero.validate().is_ok());
+        let negative = PdfOptions {
+            margins: PageMargins {
+                left: -0.1,
+                ..PageMargins::default()
+            },
+            ..PdfOptions::default()
+        };
+        assert_eq!(rejection(&negative), "margins must not be negative");
+    }
+
+    #[test]
+    fn scale_is_between_a_tenth_and_2() {
+        for scale in [0.1, 1.0, 2.0] {
+            let options = PdfOptions {
+                scale: Some(scale),
+                ..PdfOptions::default()
+            };
+            assert!(options.validate().is_ok(), "{scale}");
+        }
+        for scale in [0.09, 2.01, f64::NAN] {
+            let options = PdfOptions {
+                scale: Some(scale),
+                ..PdfOptions::default()
+            };
+            assert_eq!(
+                rejection(&options),
+                "scale must be between 0.1 and 2",
+                "{scale}"
+            );
+        }
+        assert_eq!(PdfOptions::default().command().scale, 1.0);
+    }
+
+    #[test]
+    fn one_template_hides_the_other_part() {
+        let command = PdfOptions::default().command();
+        assert!(!command.display_header_footer);
+        assert_eq!(command.header_template, None);
+
+        let command = PdfOptions {
+            footer_template: Some("<span class=\"pageNumber\"></span>".to_string()),
+            ..PdfOptions::default()
+        }
+        .command();
+        assert!(command.display_header_footer);
+        assert_eq!(command.header_template.as_deref(), Some(EMPTY_TEMPLATE));
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::RuntimeError;
+use crate::paths::app_data_subdir;
+use crate::sketchs::{BrowserProfile, BrowserProfileInfo};
+use crate::utils::now_ms;
+use tracing::warn;
+
+const PROFILE_META_FILE: &str = "profile.json";
+const USER_DATA_DIR: &str = "user-data";
+const MAX_PROFILE_NAME_LEN: usize = 64;
+
+/*
+** files a running browser keeps in its user-data dir that must not be copied
+** into a clone (they would make the copy look locked or already running)
+*/
+const SKIP_ON_CLONE: &[&str] = &[
+    "SingletonLock",
+    "SingletonSocket",
+    "SingletonCookie",
+    "lockfile",
+    "DevToolsActivePort",
+];
+
+/*
+** <app data>/profiles/<name>/{profile.json, user-data/}
+*/
+fn profiles_root() -> Result<PathBuf, RuntimeError> {
+    app_data_subdir("profiles")
+}
+
+fn validate_name(name: &str) -> Result<(), RuntimeError> {
+    let invalid = |reason: String| Err(RuntimeError::InvalidInput { reason });
+    if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return invalid(format!(
+            "Profile name must be 1 to {MAX_PROFILE_NAME_LEN} characters long"
+        ));
+    }
+    if name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+    {
+        return invalid(format!(
+            "Invalid profile name '{name}': use letters, digits, spaces, '-', '_' or '.'"
+        ));
+    }
+    Ok(())
+}
+
+fn profile_dir(name: &str) -> Result<PathBuf, RuntimeError> {
+    validate_name(name)?;
+    Ok(profiles_root()?.join(name))
+}
+
+fn read_meta(dir: &Path) -> Result<BrowserProfile, RuntimeError> {
+    let path = dir.join(PROFILE_META_FILE);
+    let raw = fs::read_to_string(&path).map_err(|e| RuntimeError::storage(&path, e))?;
+    serde_json::from_str(&raw).map_err(|e| RuntimeError::storage(&path, e))
+}
+
+/*
+** an unreadable profile.json counts as a missing profile
+*/
+fn read_existing_meta(dir: &Path, name: &str) -> Result<BrowserProfile, RuntimeError> {
+    read_meta(dir).map_err(|_| RuntimeError::ProfileNotFound {
+        profile: name.to_string(),
+    })
+}
+
+fn write_meta(dir: &Path, profile: &BrowserProfile) -> Result<(), RuntimeError> {
+    let path = dir.join(PROFILE_META_FILE);
+    let raw = serde_json::to_string_pretty(profile).map_err(RuntimeError::internal)?;
+    fs::write(&path, raw).map_err(|e| RuntimeError::storage(&path, e))
+}
+
+fn exists_error(name: &str) -> RuntimeError {
+    RuntimeError::ProfileExists {
+        profile: name.to_string(),
+    }
+}
+
+/*
+** symlinks are not followed: browsers keep their singleton markers as links
+*/
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if SKIP_ON_CLONE.iter().any(|skip| name == *skip) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &to.join(&name))?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+fn info(profile: BrowserProfile, dir: &Path, in_use: bool) -> BrowserProfileInfo {
+    BrowserProfileInfo {
+        size_bytes: dir_size(&dir.join(USER_DATA_DIR)),
+        profile,
+        in_use,
+    }
+}
+
+pub fn create_profile(name: &str, browser_id: &str) -> Result<BrowserProfileInfo, RuntimeError> {
+    let dir = profile_dir(name)?;
+    if dir.exists() {
+        return Err(exists_error(name));
+    }
+    fs::create_dir_all(dir.join(USER_DATA_DIR)).map_err(|e| RuntimeError::storage(&dir, e))?;
+
+    let profile = BrowserProfile {
+        name: name.to_string(),
+        browser_id: browser_id.to_string(),
+        created_at_ms: now_ms(),
+        last_used_ms: None,
+    };
+    write_meta(&dir, &profile)?;
+    Ok(info(profile, &dir, false))
+}
+
+/*
+** `in_use` tells which profiles currently back a running instance
+*/
+pub fn list_profiles(
+    in_use: impl Fn(&str) -> bool,
+) -> Result<Vec<BrowserProfileInfo>, RuntimeError> {
+    let root = profiles_root()?;
+    let entries = fs::read_dir(&root).map_err(|e| RuntimeError::storage(&root, e))?;
+
+    let mut profiles: Vec<BrowserProfileInfo> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| {
+            let dir = entry.path();
+            match read_meta(&dir) {
+                Ok(profile) => {
+                    let used = in_use(&profile.name);
+                    Some(info(profile, &dir, used))
+                }
+                Err(e) => {
+                    warn!("skipping profile dir {}: {e}", dir.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.profile.name.cmp(&b.profile.name));
+    Ok(profiles)
+}
+
+pub fn clone_profile(source: &str, target: &str) -> Result<BrowserProfileInfo, RuntimeError> {
+    let from = profile_dir(source)?;
+    let to = profile_dir(target)?;
+    let source_meta = read_existing_meta(&from, source)?;
+    if to.exists() {
+        return Err(exists_error(target));
+    }
+
+    if let Err(e) = copy_dir(&from.join(USER_DATA_DIR), &to.join(USER_DATA_DIR)) {
+        let _ = fs::remove_dir_all(&to);
+        return Err(RuntimeError::storage(&to, e));
+    }
+
+    let profile = BrowserProfile {
+        name: target.to_string(),
+        browser_id: source_meta.browser_id,
+        created_at_ms: now_ms(),
+        last_used_ms: None,
+    };
+    write_meta(&to, &profile)?;
+    Ok(info(profile, &to, false))
+}
+
+pub fn rename_profile(name: &str, new_name: &str) -> Result<BrowserProfileInfo, RuntimeError> {
+    let from = profile_dir(name)?;
+    let to = profile_dir(new_name)?;
+    let mut profile = read_existing_meta(&from, name)?;
+    if to.exists() {
+        return Err(exists_error(new_name));
+    }
+
+    fs::rename(&from, &to).map_err(|e| RuntimeError::storage(&from, e))?;
+    profile.name = new_name.to_string();
+    write_meta(&to, &profile)?;
+    Ok(info(profile, &to, false))
+}
+
+pub fn delete_profile(name: &str) -> Result<(), RuntimeError> {
+    let dir = profile_dir(name)?;
+    if !dir.join(PROFILE_META_FILE).exists() {
+        return Err(RuntimeError::ProfileNotFound {
+            profile: name.to_string(),
+        });
+    }
+    fs::remove_dir_all(&dir).map_err(|e| RuntimeError::storage(&dir, e))
+}
+
+/*
+** user-data dir to launch `name` with. the profile is created on first use;
+** an existing one must belong to the same browser.
+*/
+pub fn prepare_profile(name: &str, browser_id: &str) -> Result<PathBuf, RuntimeError> {
+    let dir = profile_dir(name)?;
+    let mut profile = match read_meta(&dir) {
+        Ok(profile) => profile,
+        Err(_) => create_profile(name, browser_id)?.profile,
+    };
+    if profile.browser_id != browser_id {
+        return Err(RuntimeError::ProfileBrowserMismatch {
+            profile: name.to_string(),
+            owner: profile.browser_id,
+            requested: browser_id.to_string(),
+        });
+    }
+
+    profile.last_used_ms = Some(now_ms());
+    write_meta(&dir, &profile)?;
+
+    let user_data = dir.join(USER_DATA_DIR);
+    fs::create_dir_all(&user_data).map_err(|e| RuntimeError::storage(&user_data, e))?;
+    Ok(user_data)
+}
+
+/*
+** user-data dirs of every profile we manage, for DevToolsActivePort discovery
+*/
+pub fn user_data_dirs() -> Vec<PathBuf> {
+    let Ok(entries) = profiles_root()
+        .and_then(|root| fs::read_dir(&root).map_err(|e| RuntimeError::storage(&root, e)))
+    else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path().join(USER_DATA_DIR))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
@@ -0,0 +1,638 @@
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::browser_manager::managed_cdp_session;
+use crate::cdp::{GetDocument, GetOuterHtml, GetTargetInfo};
+use crate::error::RuntimeError;
+
+pub const DEFAULT_MAX_TOKENS: usize = 4000;
+const MAX_TOKENS_LIMIT: usize = 100_000;
+
+/*
+** rough, but stable and close enough for English text with GPT-style
+** tokenizers; the budget only has to keep prompts in the right ballpark
+*/
+const CHARS_PER_TOKEN: usize = 4;
+
+/*
+** a container needs this much paragraph text to be picked as the main
+** content when the page has no <main> or <article>
+*/
+const MIN_CONTENT_CHARS: usize = 250;
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+const SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object",
+    "embed", "video", "audio", "map", "button", "input", "select", "textarea", "option", "dialog",
+    "menu",
+];
+
+/*
+** site chrome; kept when inside an <article>, where they hold its byline
+** and notes
+*/
+const CHROME_TAGS: &[&str] = &["header", "footer", "nav", "aside"];
+
+const SKIPPED_ROLES: &[&str] = &[
+    "alertdialog",
+    "banner",
+    "complementary",
+    "contentinfo",
+    "dialog",
+    "listbox",
+    "menu",
+    "menubar",
+    "navigation",
+    "progressbar",
+    "search",
+    "tooltip",
+];
+
+/*
+** whole words of a class or id that mark widgets around the content
+*/
+const BOILERPLATE_WORDS: &[&str] = &[
+    "ad",
+    "ads",
+    "advert",
+    "advertisement",
+    "banner",
+    "breadcrumb",
+    "breadcrumbs",
+    "comments",
+    "consent",
+    "cookie",
+    "cookies",
+    "gdpr",
+    "modal",
+    "newsletter",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sharing",
+    "sidebar",
+    "social",
+    "sponsored",
+    "subscribe",
+];
+
+const INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "del", "dfn", "em", "font", "i", "img",
+    "ins", "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup",
+    "time", "tt", "u", "var", "wbr",
+];
+
+/*
+** the main content of a page as Markdown. `token_estimate` is for
+** `markdown` only.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PageMarkdown {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub markdown: String,
+    pub token_estimate: usize,
+    pub truncated: bool,
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn attr<'a>(element: &ElementRef<'a>, name: &str) -> Option<&'a str> {
+    element.value().attr(name)
+}
+
+fn is_hidden(element: &ElementRef) -> bool {
+    if attr(element, "hidden").is_some() || attr(element, "aria-hidden") == Some("true") {
+        return true;
+    }
+    attr(element, "style").is_some_and(|style| {
+        let style: String = style
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    })
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    if attr(element, "role").is_some_and(|role| SKIPPED_ROLES.contains(&role)) {
+        return true;
+    }
+    [attr(element, "class"), attr(element, "id")]
+        .into_iter()
+        .flatten()
+        .flat_map(|value| value.split(|c: char| !c.is_ascii_alphanumeric()))
+        .any(|word| BOILERPLATE_WORDS.contains(&word.to_ascii_lowercase().as_str()))
+}
+
+/*
+** javascript: links and same-page anchors are dropped, the rest made
+** absolute against the page url
+*/
+fn resolve_link(base: Option<&Url>, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') {
+        return None;
+    }
+    let url = match base {
+        Some(base) => base.join(href).ok()?,
+        None => Url::parse(href).ok()?,
+    };
+    if !matches!(url.scheme(), "http" | "https" | "mailto" | "tel") {
+        return None;
+    }
+    if let Some(base) = base {
+        if url.fragment().is_some()
+            && url[..url::Position::AfterQuery] == base[..url::Position::AfterQuery]
+        {
+            return None;
+        }
+    }
+    Some(url.to_string())
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+struct Renderer<'a> {
+    base: Option<&'a Url>,
+    in_article: bool,
+    blocks: Vec<String>,
+    inline: String,
+    /*
+     ** set at the edges of generic inline elements, see push_text
+     */
+    boundary: bool,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(base: Option<&'a Url>, in_article: bool) -> Self {
+        Self {
+            base,
+            in_article,
+            blocks: Vec::new(),
+            inline: String::new(),
+            boundary: false,
+        }
+    }
+
+    /*
+     ** a run of text and inline elements ends up as one paragraph; <br>
+     ** breaks it into lines
+     */
+    fn flush(&mut self) {
+        let paragraph = self
+            .inline
+            .split('\n')
+            .map(collapse_whitespace)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.inline.clear();
+        self.push_block(paragraph);
+    }
+
+    /*
+     ** script-heavy pages often render the same label twice in a row
+     ** (a visible one and one for screen readers)
+     */
+    fn push_block(&mut self, block: String) {
+        if !block.trim().is_empty() && self.blocks.last() != Some(&block) {
+            self.blocks.push(block);
+        }
+    }
+
+    /*
+     ** app markup lays out sibling spans with CSS and no whitespace in
+     ** between ("Austrian" "Operated by ..."), so a word that starts with a
+     ** capital or a digit right after one ends gets a space
+     */
+    fn push_text(&mut self, text: &str) {
+        if text.trim().is_empty() {
+            if !self.inline.ends_with([' ', '\n']) && !self.inline.is_empty() {
+                self.inline.push(' ');
+            }
+            return;
+        }
+        let glued = self.boundary
+            && self.inline.ends_with(char::is_alphanumeric)
+            && text.starts_with(|c: char| c.is_uppercase() || c.is_ascii_digit());
+        if glued {
+            self.inline.push(' ');
+        }
+        self.boundary = false;
+        if text.starts_with(char::is_whitespace) && !self.inline.ends_with([' ', '\n']) {
+            self.inline.push(' ');
+        }
+        self.inline.push_str(&collapse_whitespace(text));
+        if text.ends_with(char::is_whitespace) {
+            self.inline.push(' ');
+        }
+    }
+
+    fn skip(&self, element: &ElementRef) -> bool {
+        let name = element.value().name();
+        SKIPPED_TAGS.contains(&name)
+            || (!self.in_article && CHROME_TAGS.contains(&name))
+            || is_hidden(element)
+            || is_boilerplate(element)
+    }
+
+    /*
+     ** everything inside `element` on one line, for headings, links and
+     ** table cells
+     */
+    fn inline_of(&self, element: ElementRef) -> String {
+        let mut renderer = Renderer::new(self.base, self.in_article);
+        renderer.children(element);
+        renderer.flush();
+        collapse_whitespace(&renderer.blocks.join(" "))
+    }
+
+    fn blocks_of(&self, element: ElementRef) -> Vec<String> {
+        let mut renderer = Renderer::new(self.base, self.in_article);
+        renderer.in_article |= element.value().name() == "article";
+        renderer.children(element);
+        renderer.flush();
+        renderer.blocks
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        if !self.skip(&child) {
+                            self.element(child);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        match name {
+            "br" => self.inline.push('\n'),
+            "img" | "wbr" => {}
+            "a" => self.link(element),
+            "strong" | "b" => self.emphasis(element, "**"),
+            "em" | "i" => self.emphasis(element, "_"),
+            "code" | "kbd" | "samp" | "tt" => {
+                let text = collapse_whitespace(&element.text().collect::<String>());
+                if !text.is_empty() {
+                    self.inline.push_str(&format!("`{text}`"));
+                }
+            }
+            name if INLINE_TAGS.contains(&name) => {
+                self.boundary = true;
+                self.children(element);
+                self.boundary = true;
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                let text = self.inline_of(element);
+                if !text.is_empty() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.push_block(format!("{} {text}", "#".repeat(level)));
+                }
+            }
+            "ul" | "ol" => {
+                self.flush();
+                let list = self.list(element, name == "ol");
+                self.push_block(list);
+            }
+            "table" => {
+                self.flush();
+                self.table(element);
+            }
+            "pre" => {
+                self.flush();
+                let code = element.text().collect::<String>();
+                let code = code.trim_matches('\n').trim_end();
+                if !code.trim().is_empty() {
+                    self.push_block(format!("```\n{code}\n```"));
+                }
+            }
+            "blockquote" => {
+                self.flush();
+                let quote = self
+                    .blocks_of(element)
+                    .join("\n\n")
+                    .lines()
+                    .map(|line| format!("> {line}").trim_end().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.push_block(quote);
+            }
+            "hr" => {
+                self.flush();
+                self.push_block("---".to_string());
+            }
+            _ => {
+                self.flush();
+                let in_article = self.in_article;
+                self.in_article |= name == "article";
+                self.children(element);
+                self.flush();
+                self.in_article = in_article;
+            }
+        }
+    }
+
+    fn emphasis(&mut self, element: ElementRef, marker: &str) {
+        let text = self.inline_of(element);
+        if !text.is_empty() {
+            self.inline.push_str(&format!("{marker}{text}{marker}"));
+        }
+    }
+
+    /*
+     ** links around images or icons fall back to their label
+     */
+    fn link(&mut self, element: ElementRef) {
+        let mut text = self.inline_of(element);
+        if text.is_empty() {
+            text = ["aria-label", "title"]
+                .iter()
+                .find_map(|name| attr(&element, name))
+                .map(collapse_whitespace)
+                .unwrap_or_default();
+        }
+        if text.is_empty() {
+            return;
+        }
+        match attr(&element, "href").and_then(|href| resolve_link(self.base, href)) {
+            Some(url) => self.inline.push_str(&format!("[{text}]({url})")),
+            None => self.inline.push_str(&text),
+        }
+    }
+
+    fn list(&self, element: ElementRef, ordered: bool) -> String {
+        let mut items = Vec::new();
+        for item in element.child_elements() {
+            if item.value().name() != "li" || self.skip(&item) {
+                continue;
+            }
+            let blocks = self.blocks_of(item);
+            if blocks.is_empty() {
+                continue;
+            }
+            let marker = if ordered {
+                format!("{}. ", items.len() + 1)
+            } else {
+                "- ".to_string()
+            };
+            let indent = " ".repeat(marker.len());
+            let body = blocks.join("\n").replace('\n', &format!("\n{indent}"));
+            items.push(format!("{marker}{body}"));
+        }
+        items.join("\n")
+    }
+
+    fn rows(table: ElementRef<'_>) -> Vec<ElementRef<'_>> {
+        let mut rows = Vec::new();
+        for child in table.child_elements() {
+            match child.value().name() {
+                "tr" => rows.push(child),
+                "thead" | "tbody" | "tfoot" => rows.extend(
+                    child
+                        .child_elements()
+                        .filter(|row| row.value().name() == "tr"),
+                ),
+                _ => {}
+            }
+        }
+        rows
+    }
+
+    /*
+     ** data tables become GFM tables with the first row as header. tables
+     ** with a single row or column, or with tables inside, are layout and
+     ** are rendered as their content.
+     */
+    fn table(&mut self, element: ElementRef) {
+        let rows: Vec<Vec<ElementRef>> = Self::rows(element)
+            .into_iter()
+            .filter(|row| !self.skip(row))
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let nested = element
+            .select(&Selector::parse("table table").expect("valid selector"))
+            .next()
+            .is_some();
+        if rows.len() < 2 || columns < 2 || nested {
+            for block in self.blocks_of(element) {
+                self.push_block(block);
+            }
+            return;
+        }
+
+        let mut lines = Vec::new();
+        for (index, cells) in rows.iter().enumerate() {
+            let mut texts: Vec<String> = cells
+                .iter()
+                .map(|cell| escape_cell(&self.inline_of(*cell)))
+                .collect();
+            texts.resize(columns, String::new());
+            lines.push(format!("| {} |", texts.join(" | ")));
+            if index == 0 {
+                lines.push(format!("|{}", " --- |".repeat(columns)));
+            }
+        }
+        self.push_block(lines.join("\n"));
+    }
+}
+
+/*
+** <main> or role=main, else the only <article>, else the element holding
+** the most paragraph text, else the whole body
+*/
+fn content_root<'a>(html: &'a Html) -> ElementRef<'a> {
+    let select = |selector: &str| {
+        html.select(&Selector::parse(selector).expect("valid selector"))
+            .filter(|element| !is_hidden(element))
+            .collect::<Vec<_>>()
+    };
+    if let Some(main) = select("main, [role=main]").into_iter().next() {
+        return main;
+    }
+    let articles = select("article");
+    if articles.len() == 1 {
+        return articles[0];
+    }
+
+    let mut scores: Vec<(ElementRef<'a>, usize)> = Vec::new();
+    let mut add = |element: ElementRef<'a>, score: usize| match scores
+        .iter_mut()
+        .find(|(known, _)| known.id() == element.id())
+    {
+        Some((_, total)) => *total += score,
+        None => scores.push((element, score)),
+    };
+    for paragraph in select("p, pre, td, blockquote") {
+        let length = collapse_whitespace(&paragraph.text().collect::<String>()).len();
+        if length < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let mut ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        if let Some(parent) = ancestors.next() {
+            add(parent, length);
+        }
+        if let Some(grandparent) = ancestors.next() {
+            add(grandparent, length / 2);
+        }
+    }
+    let best = scores
+        .into_iter()
+        .filter(|(element, _)| !matches!(element.value().name(), "html" | "body"))
+        .max_by_key(|(_, score)| *score);
+    match best {
+        Some((element, score)) if score >= MIN_CONTENT_CHARS => element,
+        _ => select("body")
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| html.root_element()),
+    }
+}
+
+fn page_title(html: &Html) -> Option<String> {
+    let select = |selector: &str| {
+        html.select(&Selector::parse(selector).expect("valid selector"))
+            .next()
+    };
+    select("title")
+        .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        .or_else(|| {
+            select("meta[property='og:title']")
+                .and_then(|meta| meta.value().attr("content"))
+                .map(collapse_whitespace)
+        })
+        .filter(|title| !title.is_empty())
+}
+
+fn page_description(html: &Html) -> Option<String> {
+    html.select(
+        &Selector::parse("meta[name=description], meta[property='og:description']")
+            .expect("valid selector"),
+    )
+    .find_map(|meta| meta.value().attr("content"))
+    .map(collapse_whitespace)
+    .filter(|description| !description.is_empty())
+}
+
+/*
+** whole blocks while they fit; a first block that alone is over budget is
+** cut at a word boundary
+*/
+fn fit_to_budget(blocks: &[String], max_tokens: usize) -> (String, bool) {
+    let max_chars = max_tokens * CHARS_PER_TOKEN;
+    let mut markdown = String::new();
+    let mut chars = 0;
+    for block in blocks {
+        let separator = if markdown.is_empty() { 0 } else { 2 };
+        let length = block.chars().count();
+        if chars + separator + length > max_chars {
+            if markdown.is_empty() {
+                let cut: String = block.chars().take(max_chars).collect();
+                let cut = match cut.rfind(char::is_whitespace) {
+                    Some(end) if end > 0 => cut[..end].trim_end().to_string(),
+                    _ => cut,
+                };
+                markdown.push_str(&cut);
+            }
+            return (markdown, true);
+        }
+        if separator > 0 {
+            markdown.push_str("\n\n");
+        }
+        markdown.push_str(block);
+        chars += separator + length;
+    }
+    (markdown, false)
+}
+
+/*
+** `url` resolves relative links and is echoed back in the result
+*/
+pub fn html_to_markdown(html: &str, url: Option<&str>, max_tokens: usize) -> PageMarkdown {
+    let document = Html::parse_document(html);
+    let base = url.and_then(|url| Url::parse(url).ok());
+    let root = content_root(&document);
+
+    let mut renderer = Renderer::new(base.as_ref(), false);
+    renderer.element(root);
+    renderer.flush();
+    let (markdown, truncated) = fit_to_budget(&renderer.blocks, max_tokens);
+
+    PageMarkdown {
+        url: url.map(str::to_string),
+        title: page_title(&document),
+        description: page_description(&document),
+        token_estimate: estimate_tokens(&markdown),
+        markdown,
+        truncated,
+    }
+}
+
+/*
+** the target's live DOM (not the HTML it was served with) as Markdown
+*/
+pub async fn page_markdown(
+    instance_id: Option<&str>,
+    target_id: &str,
+    max_tokens: Option<usize>,
+) -> Result<PageMarkdown, RuntimeError> {
+    let max_tokens = max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    if !(1..=MAX_TOKENS_LIMIT).contains(&max_tokens) {
+        return Err(RuntimeError::InvalidInput {
+            reason: format!("max tokens must be between 1 and {MAX_TOKENS_LIMIT}"),
+        });
+    }
+
+    let session = managed_cdp_session(instance_id).await?;
+    let page = session
+        .execute(&GetTargetInfo {
+            target_id: target_id.to_string(),
+        })
+        .await?
+        .target_info;
+    let target = session.attach_to_target(target_id).await?;
+    let html = async {
+        let root = target.execute(&GetDocument { depth: 0 }).await?.root;
+        target
+            .execute(&GetOuterHtml {
+                node_id: root.node_id,
+            })
+            .await
+    }
+    .await;
+    let _ = target.detach().await;
+    let html = html?.outer_html;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        html_to_markdown(&html, Some(&page.url), max_tokens)
+    })
+    .await
+    .map_err(RuntimeError::internal)
+}
@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+
+const ACTIVE_PORT_FILE: &str = "DevToolsActivePort";
+const LISTENING_PREFIX: &str = "DevTools listening on ";
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+pub const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/*
+** what a freshly started browser tells us once its DevTools server is up
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivePort {
+    pub port: u16,
+    pub ws_url: String,
+}
+
+pub fn active_port_file(user_data_dir: &Path) -> PathBuf {
+    user_data_dir.join(ACTIVE_PORT_FILE)
+}
+
+/*
+** a file left over from a previous run would make us "ready" instantly
+*/
+pub fn clear_active_port_file(user_data_dir: &Path) {
+    let _ = std::fs::remove_file(active_port_file(user_data_dir));
+}
+
+/*
+** DevToolsActivePort holds the port on the first line and the
+** browser target path ("/devtools/browser/<id>") on the second
+*/
+pub fn parse_active_port(contents: &str) -> Option<ActivePort> {
+    let mut lines = contents.lines();
+    let port: u16 = lines.next()?.trim().parse().ok()?;
+    let path = lines.next()?.trim();
+    if port == 0 || !path.starts_with('/') {
+        return None;
+    }
+    Some(ActivePort {
+        port,
+        ws_url: format!("ws://127.0.0.1:{port}{path}"),
+    })
+}
+
+/*
+** "DevTools listening on ws://127.0.0.1:9222/devtools/browser/<id>"
+*/
+pub fn parse_listening_line(line: &str) -> Option<ActivePort> {
+    let ws_url = line.trim().strip_prefix(LISTENING_PREFIX)?.trim();
+    let port = CdpEndpoint::parse(ws_url).ok()?.port;
+    Some(ActivePort {
+        port,
+        ws_url: ws_url.to_string(),
+    })
+}
+
+/*
+** line callback for the stderr reader that reports the first
+** "DevTools listening on" line
+*/
+pub fn listening_watcher() -> (impl FnMut(&str) + Send, oneshot::Receiver<ActivePort>) {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    let on_line = move |line: &str| {
+        if let Some(active) = parse_listening_line(line) {
+            if let Some(tx) = tx.take() {
+                let _ = tx.send(active);
+            }
+        }
+    };
+    (on_line, rx)
+}
+
+/*
+** wait until the browser announces its DevTools endpoint, either through the
+** DevToolsActivePort file in its profile or through stderr, whichever is first
+*/
+pub async fn wait_until_ready(
+    child: &mut Child,
+    user_data_dir: Option<&Path>,
+    mut listening: oneshot::Receiver<ActivePort>,
+    timeout: Duration,
+) -> Result<ActivePort, RuntimeError> {
+    let started = Instant::now();
+    let port_file = user_data_dir.map(active_port_file);
+
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                return Err(RuntimeError::BrowserExited {
+                    status: status.to_string(),
+                })
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return Err(RuntimeError::Internal {
+                    message: format!("Failed to check browser process status: {e}"),
+                })
+            }
+        }
+
+        if let Some(active) = port_file
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .and_then(|contents| parse_active_port(&contents))
+        {
+            return Ok(active);
+        }
+
+        if let Ok(active) = listening.try_recv() {
+            return Ok(active);
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(RuntimeError::LaunchTimeout {
+                seconds: timeout.as_secs(),
+            });
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,951 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Child;
+
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::browser_logs::BrowserLogs;
+use crate::browser_manager::LaunchSpec;
+use crate::cdp::CdpSession;
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+use crate::sketchs::{BrowserInstanceInfo, ManageableBrowserInstance};
+
+pub static BROWSER_MANAGER: Lazy<BrowserManager> = Lazy::new(BrowserManager::spawn);
+
+/*
+** what a caller may keep of an instance once the actor has answered.
+** `cdp` is only set while the session is still connected, `exited` once the
+** process we launched is gone.
+*/
+#[derive(Debug, Clone)]
+pub struct InstanceSnapshot {
+    pub instance_id: String,
+    pub path: String,
+    pub endpoint: Option<CdpEndpoint>,
+    pub ws_url: Option<String>,
+    pub cdp: Option<CdpSession>,
+    pub logs: Option<BrowserLogs>,
+    pub exited: Option<String>,
+    pub restarting: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum InstanceKey {
+    Path(String),
+    /*
+     ** only endpoints on this machine can hold one of our local ports
+     */
+    Port(u16),
+    WsUrl(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TakeIf {
+    Any,
+    LaunchedByApp,
+}
+
+#[derive(Debug)]
+pub struct TakenInstance {
+    pub instance_id: String,
+    pub instance: ManageableBrowserInstance,
+}
+
+/*
+** what the watchdog gets back when it marks an instance as crashed. the
+** registry entry itself stays, so its id and profile remain taken and a
+** close request can still find it while the browser is down.
+*/
+#[derive(Debug)]
+pub struct CrashedInstance {
+    pub child: Option<Child>,
+    pub browser_id: String,
+    pub ws_url: Option<String>,
+    pub launch: Option<LaunchSpec>,
+}
+
+/*
+** held for the length of a launch so a second launch on the same profile
+** fails fast instead of racing the first; released on drop
+*/
+#[derive(Debug)]
+pub struct ProfileReservation {
+    profile: String,
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl Drop for ProfileReservation {
+    fn drop(&mut self) {
+        let _ = self
+            .sender
+            .send(Message::ReleaseProfile(std::mem::take(&mut self.profile)));
+    }
+}
+
+type Reply<T> = oneshot::Sender<T>;
+
+enum Message {
+    List(Reply<Vec<BrowserInstanceInfo>>),
+    Select {
+        instance_id: String,
+        reply: Reply<Result<(), RuntimeError>>,
+    },
+    Snapshot {
+        instance_id: Option<String>,
+        reply: Reply<Result<InstanceSnapshot, RuntimeError>>,
+    },
+    Find {
+        key: InstanceKey,
+        reply: Reply<Option<String>>,
+    },
+    Insert {
+        instance: Box<ManageableBrowserInstance>,
+        reply: Reply<String>,
+    },
+    Crash {
+        instance_id: String,
+        reply: Reply<Option<CrashedInstance>>,
+    },
+    Restore {
+        instance_id: String,
+        instance: Box<ManageableBrowserInstance>,
+        reply: Reply<Result<(), Box<ManageableBrowserInstance>>>,
+    },
+    Adopt {
+        instance: Box<ManageableBrowserInstance>,
+        reply: Reply<(String, bool)>,
+    },
+    SetCdp {
+        instance_id: String,
+        ws_url: String,
+        session: CdpSession,
+    },
+    Take {
+        instance_id: Option<String>,
+        only: TakeIf,
+        reply: Reply<Result<Option<TakenInstance>, RuntimeError>>,
+    },
+    TakeLaunchedByApp(Reply<Vec<ManageableBrowserInstance>>),
+    ReapPort {
+        port: u16,
+        path: String,
+        reply: Reply<Option<String>>,
+    },
+    CheckProfile {
+        profile: String,
+        reply: Reply<Result<(), RuntimeError>>,
+    },
+    ReserveProfile {
+        profile: String,
+        reply: Reply<Result<(), RuntimeError>>,
+    },
+    ReleaseProfile(String),
+    ProfilesInUse(Reply<HashSet<String>>),
+}
+
+/*
+** every browser we know about, keyed by instance id ("chrome-1", "edge-2", ...).
+** one of them is "selected" and is used when a command gets no instance id.
+** only the actor thread touches it.
+*/
+#[derive(Debug, Default)]
+struct BrowserRegistry {
+    instances: HashMap<String, ManageableBrowserInstance>,
+    selected: Option<String>,
+    next_seq: u32,
+    reserved_profiles: HashSet<String>,
+    restarting: HashSet<String>,
+}
+
+impl BrowserRegistry {
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::List(reply) => {
+                let _ = reply.send(self.list());
+            }
+            Message::Select { instance_id, reply } => {
+                let _ = reply.send(self.select(&instance_id));
+            }
+            Message::Snapshot { instance_id, reply } => {
+                let _ = reply.send(self.snapshot(instance_id.as_deref()));
+            }
+            Message::Find { key, reply } => {
+                let _ = reply.send(self.find(&key));
+            }
+            Message::Insert { instance, reply } => {
+                let _ = reply.send(self.insert(*instance));
+            }
+            Message::Crash { instance_id, reply } => {
+                let _ = reply.send(self.crash(&instance_id));
+            }
+            Message::Restore {
+                instance_id,
+                instance,
+                reply,
+            } => {
+                let _ = reply.send(self.restore(instance_id, instance));
+            }
+            Message::Adopt { instance, reply } => {
+                let adopted = match self.find(&InstanceKey::WsUrl(
+                    instance.ws_url.clone().unwrap_or_default(),
+                )) {
+                    Some(instance_id) => {
+                        self.selected = Some(instance_id.clone());
+                        (instance_id, false)
+                    }
+                    None => (self.insert(*instance), true),
+                };
+                let _ = reply.send(adopted);
+            }
+            Message::SetCdp {
+                instance_id,
+                ws_url,
+                session,
+            } => {
+                /*
+                 ** the instance may have been restarted on a new url meanwhile
+                 */
+                if let Some(instance) = self
+                    .instances
+                    .get_mut(&instance_id)
+                    .filter(|i| i.ws_url.as_deref() == Some(ws_url.as_str()))
+                {
+                    instance.cdp = Some(session);
+                }
+            }
+            Message::Take {
+                instance_id,
+                only,
+                reply,
+            } => {
+                let _ = reply.send(self.take(instance_id.as_deref(), only));
+            }
+            Message::TakeLaunchedByApp(reply) => {
+                let ids: Vec<String> = self
+                    .instances
+                    .iter()
+                    .filter(|(_, instance)| instance.launched_by_app)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                let taken = ids.iter().filter_map(|id| self.remove(id)).collect();
+                let _ = reply.send(taken);
+            }
+            Message::ReapPort { port, path, reply } => {
+                let _ = reply.send(self.reap_port(port, &path));
+            }
+            Message::CheckProfile { profile, reply } => {
+                let _ = reply.send(self.check_profile(&profile));
+            }
+            Message::ReserveProfile { profile, reply } => {
+                let reserved = self.check_profile(&profile);
+                if reserved.is_ok() {
+                    self.reserved_profiles.insert(profile);
+                }
+                let _ = reply.send(reserved);
+            }
+            Message::ReleaseProfile(profile) => {
+                self.reserved_profiles.remove(&profile);
+            }
+            Message::ProfilesInUse(reply) => {
+                let mut profiles = self.reserved_profiles.clone();
+                profiles.extend(
+                    self.instances
+                        .values()
+                        .filter_map(|i| i.launch.as_ref()?.profile.clone()),
+                );
+                let _ = reply.send(profiles);
+            }
+        }
+    }
+
+    /*
+     ** store a new instance, select it and hand back its id
+     */
+    fn insert(&mut self, instance: ManageableBrowserInstance) -> String {
+        self.next_seq += 1;
+        let instance_id = format!("{}-{}", instance.browser_id, self.next_seq);
+        self.instances.insert(instance_id.clone(), instance);
+        self.selected = Some(instance_id.clone());
+        instance_id
+    }
+
+    fn remove(&mut self, instance_id: &str) -> Option<ManageableBrowserInstance> {
+        if self.selected.as_deref() == Some(instance_id) {
+            self.selected = None;
+        }
+        self.restarting.remove(instance_id);
+        self.instances.remove(instance_id)
+    }
+
+    /*
+     ** keep the entry (and with it the id, selection and profile) but hand
+     ** its dead process to the watchdog
+     */
+    fn crash(&mut self, instance_id: &str) -> Option<CrashedInstance> {
+        let instance = self.instances.get_mut(instance_id)?;
+        instance.cdp = None;
+        self.restarting.insert(instance_id.to_string());
+        Some(CrashedInstance {
+            child: instance.child.take(),
+            browser_id: instance.browser_id.clone(),
+            ws_url: instance.ws_url.clone(),
+            launch: instance.launch.clone(),
+        })
+    }
+
+    /*
+     ** only an entry still waiting for its restart takes the new process;
+     ** if it was closed meanwhile the caller gets the process back
+     */
+    fn restore(
+        &mut self,
+        instance_id: String,
+        instance: Box<ManageableBrowserInstance>,
+    ) -> Result<(), Box<ManageableBrowserInstance>> {
+        if !self.restarting.remove(&instance_id) {
+            return Err(instance);
+        }
+        self.instances.insert(instance_id, *instance);
+        Ok(())
+    }
+
+    fn select(&mut self, instance_id: &str) -> Result<(), RuntimeError> {
+        if !self.instances.contains_key(instance_id) {
+            return Err(RuntimeError::InstanceNotFound {
+                instance_id: instance_id.to_string(),
+            });
+        }
+        self.selected = Some(instance_id.to_string());
+        Ok(())
+    }
+
+    /*
+     ** explicit id if given, otherwise the selected instance
+     */
+    fn resolve(&self, instance_id: Option<&str>) -> Result<String, RuntimeError> {
+        match instance_id {
+            Some(id) if self.instances.contains_key(id) => Ok(id.to_string()),
+            Some(id) => Err(RuntimeError::InstanceNotFound {
+                instance_id: id.to_string(),
+            }),
+            None => self
+                .selected
+                .clone()
+                .ok_or(RuntimeError::NoInstanceSelected),
+        }
+    }
+
+    fn snapshot(&mut self, instance_id: Option<&str>) -> Result<InstanceSnapshot, RuntimeError> {
+        let instance_id = self.resolve(instance_id)?;
+        let instance =
+            self.instances
+                .get_mut(&instance_id)
+                .ok_or_else(|| RuntimeError::InstanceNotFound {
+                    instance_id: instance_id.clone(),
+                })?;
+        let exited = instance
+            .child
+            .as_mut()
+            .and_then(|child| match child.try_wait() {
+                Ok(Some(status)) => Some(format!("process exited ({status})")),
+                Ok(None) => None,
+                Err(e) => Some(format!("failed to check process status: {e}")),
+            });
+        Ok(InstanceSnapshot {
+            path: instance.path.clone(),
+            endpoint: instance.endpoint.clone(),
+            ws_url: instance.ws_url.clone(),
+            cdp: instance.cdp.clone().filter(|s| s.is_connected()),
+            logs: instance.logs.clone(),
+            exited,
+            restarting: self.restarting.contains(&instance_id),
+            instance_id,
+        })
+    }
+
+    fn find(&self, key: &InstanceKey) -> Option<String> {
+        self.instances
+            .iter()
+            .find(|(_, instance)| match key {
+                InstanceKey::Path(path) => instance.path == *path,
+                InstanceKey::Port(port) => instance
+                    .endpoint
+                    .as_ref()
+                    .is_some_and(|e| e.is_local() && e.port == *port),
+                InstanceKey::WsUrl(ws_url) => instance.ws_url.as_deref() == Some(ws_url.as_str()),
+            })
+            .map(|(id, _)| id.clone())
+    }
+
+    /*
+     ** without an id and nothing selected there is nothing to take, which is
+     ** not an error; an unknown explicit id is
+     */
+    fn take(
+        &mut self,
+        instance_id: Option<&str>,
+        only: TakeIf,
+    ) -> Result<Option<TakenInstance>, RuntimeError> {
+        let instance_id = match self.resolve(instance_id) {
+            Ok(instance_id) => instance_id,
+            Err(_) if instance_id.is_none() => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if only == TakeIf::LaunchedByApp
+            && !self
+                .instances
+                .get(&instance_id)
+                .is_some_and(|instance| instance.launched_by_app)
+        {
+            return Ok(None);
+        }
+        Ok(self.remove(&instance_id).map(|instance| TakenInstance {
+            instance_id,
+            instance,
+        }))
+    }
+
+    /*
+     ** forget a dead instance on `port`; a live one of another browser keeps
+     ** it and is described in the answer
+     */
+    fn reap_port(&mut self, port: u16, path: &str) -> Option<String> {
+        let instance_id = self.find(&InstanceKey::Port(port))?;
+        let instance = self.instances.get_mut(&instance_id)?;
+        let exited = instance
+            .child
+            .as_mut()
+            .is_some_and(|child| !matches!(child.try_wait(), Ok(None)));
+        if exited {
+            self.remove(&instance_id);
+            None
+        } else if instance.path != path {
+            Some(format!(
+                "browser instance {instance_id} ({})",
+                instance.path
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn check_profile(&self, profile: &str) -> Result<(), RuntimeError> {
+        if let Some((instance_id, _)) = self.instances.iter().find(|(_, instance)| {
+            instance
+                .launch
+                .as_ref()
+                .is_some_and(|l| l.profile.as_deref() == Some(profile))
+        }) {
+            return Err(RuntimeError::ProfileInUse {
+                profile: profile.to_string(),
+                instance_id: instance_id.clone(),
+            });
+        }
+        if self.reserved_profiles.contains(profile) {
+            return Err(RuntimeError::LaunchInProgress {
+                profile: profile.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Vec<BrowserInstanceInfo> {
+        let mut list: Vec<BrowserInstanceInfo> = self
+            .instances
+            .iter()
+            .map(|(id, instance)| BrowserInstanceInfo {
+                instance_id: id.clone(),
+                browser_id: instance.browser_id.clone(),
+                path: instance.path.clone(),
+                transport: instance.transport,
+                endpoint: instance.endpoint.clone(),
+                ws_url: instance.ws_url.clone(),
+                profile: instance.launch.as_ref().and_then(|l| l.profile.clone()),
+                launched_by_app: instance.launched_by_app,
+                cdp_connected: instance.cdp.as_ref().is_some_and(|s| s.is_connected()),
+                selected: self.selected.as_deref() == Some(id.as_str()),
+                restarting: self.restarting.contains(id),
+            })
+            .collect();
+        list.sort_by(|a, b| a.instance_id.cmp(&b.instance_id));
+        list
+    }
+}
+
+/*
+** handle to the actor that owns the registry. the actor only ever does
+** bookkeeping; probes, launches and CDP handshakes run in the caller, so a
+** slow one never holds up anybody else's status query.
+*/
+#[derive(Debug, Clone)]
+pub struct BrowserManager {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl BrowserManager {
+    /*
+     ** the actor gets its own thread rather than a task so it doesn't depend on
+     ** which runtime happens to touch the manager first
+     */
+    pub fn spawn() -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("browser-manager".to_string())
+            .spawn(move || {
+                let mut registry = BrowserRegistry::default();
+                while let Some(message) = receiver.blocking_recv() {
+                    registry.handle(message);
+                }
+            })
+            .expect("failed to start the browser manager thread");
+        Self { sender }
+    }
+
+    fn post(&self, message: Message) {
+        self.sender
+            .send(message)
+            .expect("browser manager thread is gone");
+    }
+
+    async fn request<T>(&self, message: impl FnOnce(Reply<T>) -> Message) -> T {
+        let (reply, response) = oneshot::channel();
+        self.post(message(reply));
+        response.await.expect("browser manager dropped a request")
+    }
+
+    pub async fn list(&self) -> Vec<BrowserInstanceInfo> {
+        self.request(Message::List).await
+    }
+
+    pub async fn select(&self, instance_id: &str) -> Result<(), RuntimeError> {
+        let instance_id = instance_id.to_string();
+        self.request(|reply| Message::Select { instance_id, reply })
+            .await
+    }
+
+    /*
+     ** explicit id if given, otherwise the selected instance
+     */
+    pub async fn snapshot(
+        &self,
+        instance_id: Option<&str>,
+    ) -> Result<InstanceSnapshot, RuntimeError> {
+        let instance_id = instance_id.map(str::to_string);
+        self.request(|reply| Message::Snapshot { instance_id, reply })
+            .await
+    }
+
+    pub async fn find(&self, key: InstanceKey) -> Option<String> {
+        self.request(|reply| Message::Find { key, reply }).await
+    }
+
+    /*
+     ** store a new instance, select it and hand back its id
+     */
+    pub async fn insert(&self, instance: ManageableBrowserInstance) -> String {
+        self.request(|reply| Message::Insert {
+            instance: Box::new(instance),
+            reply,
+        })
+        .await
+    }
+
+    /*
+     ** mark an instance as down and waiting for a restart; None if it is gone
+     */
+    pub async fn crash(&self, instance_id: &str) -> Option<CrashedInstance> {
+        let instance_id = instance_id.to_string();
+        self.request(|reply| Message::Crash { instance_id, reply })
+            .await
+    }
+
+    /*
+     ** put a restarted instance back under its old id, unless that instance
+     ** was closed while it was down; then the new one is handed back
+     */
+    pub async fn restore(
+        &self,
+        instance_id: &str,
+        instance: ManageableBrowserInstance,
+    ) -> Result<(), Box<ManageableBrowserInstance>> {
+        let instance_id = instance_id.to_string();
+        self.request(|reply| Message::Restore {
+            instance_id,
+            instance: Box::new(instance),
+            reply,
+        })
+        .await
+    }
+
+    /*
+     ** register an instance unless one with the same ws url already is; either
+     ** way that one ends up selected. true when `instance` was inserted.
+     */
+    pub async fn adopt(&self, instance: ManageableBrowserInstance) -> (String, bool) {
+        self.request(|reply| Message::Adopt {
+            instance: Box::new(instance),
+            reply,
+        })
+        .await
+    }
+
+    pub async fn take(
+        &self,
+        instance_id: Option<&str>,
+        only: TakeIf,
+    ) -> Result<Option<TakenInstance>, RuntimeError> {
+        let instance_id = instance_id.map(str::to_string);
+        self.request(|reply| Message::Take {
+            instance_id,
+            only,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn remove(&self, instance_id: &str) -> Option<ManageableBrowserInstance> {
+        self.take(Some(instance_id), TakeIf::Any)
+            .await
+            .ok()
+            .flatten()
+            .map(|taken| taken.instance)
+    }
+
+    pub async fn take_launched_by_app(&self) -> Vec<ManageableBrowserInstance> {
+        self.request(Message::TakeLaunchedByApp).await
+    }
+
+    /*
+     ** who else holds `port`, after forgetting any instance of ours that died on it
+     */
+    pub async fn reap_port(&self, port: u16, path: &str) -> Option<String> {
+        let path = path.to_string();
+        self.request(|reply| Message::ReapPort { port, path, reply })
+            .await
+    }
+
+    pub async fn check_profile(&self, profile: &str) -> Result<(), RuntimeError> {
+        let profile = profile.to_string();
+        self.request(|reply| Message::CheckProfile { profile, reply })
+            .await
+    }
+
+    pub async fn reserve_profile(&self, profile: &str) -> Result<ProfileReservation, RuntimeError> {
+        let name = profile.to_string();
+        self.request(|reply| Message::ReserveProfile {
+            profile: name,
+            reply,
+        })
+        .await?;
+        Ok(ProfileReservation {
+            profile: profile.to_string(),
+            sender: self.sender.clone(),
+        })
+    }
+
+    /*
+     ** profiles of running instances and of launches still in flight
+     */
+    pub async fn profiles_in_use(&self) -> HashSet<String> {
+        self.request(Message::ProfilesInUse).await
+    }
+
+    /*
+     ** CDP session of an instance (or the selected one), (re)connecting lazily if needed
+     */
+    pub async fn cdp_session(&self, instance_id: Option<&str>) -> Result<CdpSession, RuntimeError> {
+        let instance = self.snapshot(instance_id).await?;
+        if let Some(session) = instance.cdp {
+            return Ok(session);
+        }
+        if instance.restarting {
+            return Err(RuntimeError::CdpUnavailable {
+                reason: format!("browser instance {} is restarting", instance.instance_id),
+            });
+        }
+        /*
+         ** a pipe can't be reopened; once it's closed the browser is gone
+         */
+        let ws_url = instance
+            .ws_url
+            .ok_or_else(|| RuntimeError::CdpUnavailable {
+                reason: format!(
+                    "debugging pipe of browser instance {} is closed",
+                    instance.instance_id
+                ),
+            })?;
+
+        let session = CdpSession::connect(&ws_url).await?;
+        self.post(Message::SetCdp {
+            instance_id: instance.instance_id,
+            ws_url,
+            session: session.clone(),
+        });
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use futures_util::future::join_all;
+
+    use super::*;
+    use crate::browser_manager::{LaunchSpec, LaunchTransport};
+    use crate::launch_options::LaunchOptions;
+    use crate::sketchs::DebugTransport;
+
+    fn instance(ws_url: &str, profile: Option<&str>) -> ManageableBrowserInstance {
+        ManageableBrowserInstance {
+            child: None,
+            browser_id: "chrome".to_string(),
+            path: "/usr/bin/google-chrome".to_string(),
+            transport: DebugTransport::Tcp,
+            endpoint: CdpEndpoint::parse(ws_url).ok(),
+            ws_url: Some(ws_url.to_string()),
+            launched_by_app: true,
+            cdp: None,
+            logs: None,
+            launch: profile.map(|profile| LaunchSpec {
+                path: "/usr/bin/google-chrome".to_string(),
+                transport: LaunchTransport::Port(9522),
+                log_to_file: false,
+                profile: Some(profile.to_string()),
+                options: LaunchOptions::default(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn status_answers_while_a_cdp_handshake_hangs() {
+        /*
+         ** accepts the TCP connection, never answers the websocket upgrade
+         */
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let manager = BrowserManager::spawn();
+        manager
+            .insert(instance(
+                &format!("ws://127.0.0.1:{port}/devtools/browser/hang"),
+                None,
+            ))
+            .await;
+
+        let handshake = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.cdp_session(None).await }
+        });
+        /*
+         ** once the connection is accepted the handshake is in flight and stays there
+         */
+        let (_socket, _) = tokio::task::spawn_blocking(move || listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let list = tokio::time::timeout(Duration::from_secs(5), manager.list())
+            .await
+            .expect("status query waited for the handshake");
+        assert_eq!(list.len(), 1);
+        assert!(!handshake.is_finished());
+        handshake.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_launches_reserve_a_profile_once() {
+        let manager = BrowserManager::spawn();
+        let attempts = join_all((0..16).map(|_| {
+            let manager = manager.clone();
+            async move { manager.reserve_profile("work").await }
+        }))
+        .await;
+
+        let (won, lost): (Vec<_>, Vec<_>) = attempts.into_iter().partition(Result::is_ok);
+        assert_eq!(won.len(), 1);
+        assert!(lost.iter().all(|attempt| matches!(
+            attempt,
+            Err(RuntimeError::LaunchInProgress { profile }) if profile == "work"
+        )));
+        assert!(manager.profiles_in_use().await.contains("work"));
+
+        /*
+         ** a failed launch gives the profile back
+         */
+        drop(won);
+        assert!(manager.check_profile("work").await.is_ok());
+        assert!(manager.profiles_in_use().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_launched_profile_stays_taken_after_the_reservation() {
+        let manager = BrowserManager::spawn();
+        let reservation = manager.reserve_profile("work").await.unwrap();
+        let instance_id = manager
+            .insert(instance(
+                "ws://127.0.0.1:9522/devtools/browser/a",
+                Some("work"),
+            ))
+            .await;
+        drop(reservation);
+
+        match manager.reserve_profile("work").await {
+            Err(RuntimeError::ProfileInUse {
+                profile,
+                instance_id: owner,
+            }) => {
+                assert_eq!(profile, "work");
+                assert_eq!(owner, instance_id);
+            }
+            other => panic!("expected ProfileInUse, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_crashed_instance_keeps_its_profile_until_closed() {
+        let manager = BrowserManager::spawn();
+        let instance_id = manager
+            .insert(instance(
+                "ws://127.0.0.1:9522/devtools/browser/a",
+                Some("work"),
+            ))
+            .await;
+        assert!(manager.crash(&instance_id).await.is_some());
+
+        assert!(
+            manager
+                .snapshot(Some(&instance_id))
+                .await
+                .unwrap()
+                .restarting
+        );
+        assert!(matches!(
+            manager.reserve_profile("work").await,
+            Err(RuntimeError::ProfileInUse { .. })
+        ));
+
+        /*
+         ** a close while the browser is down wins over the relaunch
+         */
+        manager.remove(&instance_id).await.unwrap();
+        let relaunched = instance("ws://127.0.0.1:9522/devtools/browser/b", Some("work"));
+        assert!(manager.restore(&instance_id, relaunched).await.is_err());
+        assert!(manager.list().await.is_empty());
+        assert!(manager.reserve_profile("work").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_restored_instance_is_no_longer_restarting() {
+        let manager = BrowserManager::spawn();
+        let instance_id = manager
+            .insert(instance("ws://127.0.0.1:9522/devtools/browser/a", None))
+            .await;
+        manager.crash(&instance_id).await.unwrap();
+
+        let relaunched = instance("ws://127.0.0.1:9522/devtools/browser/b", None);
+        manager.restore(&instance_id, relaunched).await.unwrap();
+        let list = manager.list().await;
+        assert_eq!(list.len(), 1);
+        assert!(list[0].selected && !list[0].restarting);
+        assert_eq!(
+            list[0].ws_url.as_deref(),
+            Some("ws://127.0.0.1:9522/devtools/browser/b")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_disconnects_take_an_instance_once() {
+        let manager = BrowserManager::spawn();
+        let instance_id = manager
+            .insert(instance("ws://127.0.0.1:9522/devtools/browser/a", None))
+            .await;
+
+        let taken = join_all((0..16).map(|i| {
+            let manager = manager.clone();
+            let instance_id = instance_id.clone();
+            async move {
+                /*
+                 ** half by id, half through the selection
+                 */
+                let id = (i % 2 == 0).then_some(instance_id.as_str());
+                manager.take(id, TakeIf::Any).await
+            }
+        }))
+        .await;
+
+        let removed: Vec<TakenInstance> = taken
+            .into_iter()
+            .filter_map(|result| match result {
+                Ok(taken) => taken,
+                Err(RuntimeError::InstanceNotFound { .. }) => None,
+                Err(e) => panic!("unexpected error: {e}"),
+            })
+            .collect();
+        assert_eq!(removed.len(), 1);
+        assert!(manager.list().await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn launch_racing_disconnect_leaves_a_consistent_registry() {
+        for _ in 0..20 {
+            let manager = BrowserManager::spawn();
+            let launch = tokio::spawn({
+                let manager = manager.clone();
+                async move {
+                    let reservation = manager.reserve_profile("work").await.unwrap();
+                    tokio::task::yield_now().await;
+                    let instance_id = manager
+                        .insert(instance(
+                            "ws://127.0.0.1:9522/devtools/browser/a",
+                            Some("work"),
+                        ))
+                        .await;
+                    drop(reservation);
+                    instance_id
+                }
+            });
+            let disconnect = tokio::spawn({
+                let manager = manager.clone();
+                async move {
+                    let mut taken = 0;
+                    for _ in 0..10 {
+                        if manager.take(None, TakeIf::Any).await.unwrap().is_some() {
+                            taken += 1;
+                        }
+                        tokio::task::yield_now().await;
+                    }
+                    taken
+                }
+            });
+
+            let instance_id = launch.await.unwrap();
+            let taken = disconnect.await.unwrap();
+            let remaining = manager.list().await;
+            assert_eq!(taken + remaining.len(), 1);
+            assert!(remaining
+                .iter()
+                .all(|i| i.instance_id == instance_id && i.selected));
+            assert_eq!(manager.check_profile("work").await.is_ok(), taken == 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_adopts_of_one_endpoint_register_it_once() {
+        let manager = BrowserManager::spawn();
+        let adopted = join_all((0..16).map(|_| {
+            let manager = manager.clone();
+            async move {
+                manager
+                    .adopt(instance("ws://127.0.0.1:9222/devtools/browser/x", None))
+                    .await
+            }
+        }))
+        .await;
+
+        assert_eq!(adopted.iter().filter(|(_, inserted)| *inserted).count(), 1);
+        assert!(adopted.iter().all(|(id, _)| *id == adopted[0].0));
+        assert_eq!(manager.list().await.len(), 1);
+    }
+}
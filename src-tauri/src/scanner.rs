@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use futures_util::stream::{self, StreamExt};
+use reqwest::Client;
+
+use crate::browser_version::BrowserVersion;
+use crate::endpoint::CdpEndpoint;
+use crate::http::{self, HttpProfile};
+use crate::platform::default_user_data_dirs;
+use crate::port_policy::port_policy;
+use crate::profiles;
+use crate::readiness::{active_port_file, parse_active_port};
+use crate::sketchs::{DiscoveredEndpoint, EndpointSource};
+use tracing::info;
+
+const MAX_CONCURRENT_PROBES: usize = 64;
+
+/*
+** ports announced through DevToolsActivePort, with the profile that announced them
+*/
+fn active_port_files() -> Vec<(u16, PathBuf)> {
+    default_user_data_dirs()
+        .into_iter()
+        .chain(profiles::user_data_dirs())
+        .filter_map(|dir| {
+            let contents = std::fs::read_to_string(active_port_file(&dir)).ok()?;
+            let active = parse_active_port(&contents)?;
+            Some((active.port, dir))
+        })
+        .collect()
+}
+
+async fn probe(
+    client: &Client,
+    port: u16,
+    user_data_dir: Option<PathBuf>,
+) -> Option<DiscoveredEndpoint> {
+    let endpoint = CdpEndpoint::local(port);
+    let version: serde_json::Value = client
+        .get(endpoint.http_url("/json/version"))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    let version = BrowserVersion::from_json_version(&version).ok()?;
+    let ws_url = version.ws_url.clone()?;
+
+    let target_count = match client.get(endpoint.http_url("/json/list")).send().await {
+        Ok(resp) => resp
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .map(|targets| targets.len())
+            .unwrap_or(0),
+        Err(_) => 0,
+    };
+
+    Some(DiscoveredEndpoint {
+        port,
+        ws_url,
+        /*
+         ** the user-data dir of an active-port hit tells Brave and friends from Chrome
+         */
+        browser_type: version
+            .family(user_data_dir.as_deref().and_then(|dir| dir.to_str()))
+            .as_str()
+            .to_string(),
+        compatibility: version.compatibility(),
+        version: version.version,
+        product: version.product,
+        user_agent: version.user_agent,
+        target_count,
+        source: if user_data_dir.is_some() {
+            EndpointSource::ActivePortFile
+        } else {
+            EndpointSource::PortScan
+        },
+        user_data_dir: user_data_dir.map(|dir| dir.display().to_string()),
+    })
+}
+
+/*
+** every DevTools endpoint we can find, optionally only of one browser type.
+** all ports are probed at once, so a full scan takes about one probe timeout.
+*/
+pub async fn scan_for_endpoints(browser_type: Option<&str>) -> Vec<DiscoveredEndpoint> {
+    let client = http::client(HttpProfile::Probe);
+
+    let mut ports: BTreeMap<u16, Option<PathBuf>> = port_policy()
+        .scan_ports()
+        .into_iter()
+        .map(|port| (port, None))
+        .collect();
+    for (port, dir) in active_port_files() {
+        ports.insert(port, Some(dir));
+    }
+
+    let client = &client;
+    let mut endpoints: Vec<DiscoveredEndpoint> = stream::iter(ports)
+        .map(|(port, dir)| probe(client, port, dir))
+        .buffer_unordered(MAX_CONCURRENT_PROBES)
+        .filter_map(|endpoint| async move { endpoint })
+        .filter(|endpoint| {
+            let keep = browser_type.is_none_or(|t| t == endpoint.browser_type);
+            async move { keep }
+        })
+        .collect()
+        .await;
+
+    endpoints.sort_by_key(|endpoint| endpoint.port);
+    endpoints
+}
+
+/*
+** websocket url of the first endpoint of `target_browser_type`
+*/
+pub async fn scan_for_existing_browser_instances(target_browser_type: &str) -> Option<String> {
+    let endpoint = scan_for_endpoints(Some(target_browser_type))
+        .await
+        .into_iter()
+        .next()?;
+    info!(
+        "Found existing {} instance on port {}: {}",
+        endpoint.browser_type, endpoint.port, endpoint.ws_url
+    );
+    Some(endpoint.ws_url)
+}
@@ -0,0 +1,368 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::browser_manager::managed_cdp_session;
+use crate::cdp::{
+    ActivateTarget, CaptureScreenshot, Evaluate, GetLayoutMetrics, TargetSession, Viewport,
+};
+use crate::error::RuntimeError;
+use crate::paths::app_data_subdir;
+use crate::utils::now_ms;
+
+const SCREENSHOTS_DIR: &str = "screenshots";
+const MAX_SCALE: f64 = 4.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/*
+** what to capture. coordinates are CSS pixels from the top-left of the
+** document, not of the viewport, so a clip below the fold works too.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureArea {
+    #[default]
+    Viewport,
+    FullPage,
+    Element {
+        selector: String,
+    },
+    Clip {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+/*
+** `quality` (0-100) only applies to jpeg and webp. with `save` the image is
+** written under the app data dir and only its path comes back.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ScreenshotOptions {
+    pub area: CaptureArea,
+    pub format: ImageFormat,
+    pub quality: Option<u8>,
+    pub scale: Option<f64>,
+    pub save: bool,
+}
+
+impl ScreenshotOptions {
+    fn validate(&self) -> Result<(), RuntimeError> {
+        let invalid = |reason: String| Err(RuntimeError::InvalidInput { reason });
+        if self.quality.is_some() && self.format == ImageFormat::Png {
+            return invalid("quality only applies to jpeg and webp screenshots".to_string());
+        }
+        if self.quality.is_some_and(|quality| quality > 100) {
+            return invalid("quality must be between 0 and 100".to_string());
+        }
+        if self
+            .scale
+            .is_some_and(|scale| !(scale > 0.0 && scale <= MAX_SCALE))
+        {
+            return invalid(format!(
+                "scale must be greater than 0 and at most {MAX_SCALE}"
+            ));
+        }
+        match &self.area {
+            CaptureArea::Element { selector } if selector.trim().is_empty() => {
+                invalid("selector must not be empty".to_string())
+            }
+            CaptureArea::Clip { width, height, .. } if !(*width > 0.0 && *height > 0.0) => {
+                invalid("clip width and height must be greater than 0".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/*
+** `data` is base64 unless the image was saved, then `path` is set instead.
+** width and height are the captured region in CSS pixels, before `scale`.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Screenshot {
+    pub target_id: String,
+    pub format: ImageFormat,
+    pub mime_type: String,
+    pub width: f64,
+    pub height: f64,
+    pub scale: f64,
+    pub captured_at_ms: u64,
+    pub data: Option<String>,
+    pub path: Option<String>,
+}
+
+/*
+** the element's border box in document coordinates, or null
+*/
+fn element_rect_script(selector: &str) -> String {
+    let selector = serde_json::Value::from(selector).to_string();
+    format!(
+        "(() => {{ \
+            const el = document.querySelector({selector}); \
+            if (!el) return null; \
+            const r = el.getBoundingClientRect(); \
+            return {{ x: r.left + window.scrollX, y: r.top + window.scrollY, width: r.width, height: r.height }}; \
+        }})()"
+    )
+}
+
+#[derive(Deserialize)]
+struct ElementRect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+async fn resolve_clip(
+    target: &TargetSession,
+    area: &CaptureArea,
+    scale: f64,
+) -> Result<Viewport, RuntimeError> {
+    let clip = |x, y, width, height| Viewport {
+        x,
+        y,
+        width,
+        height,
+        scale,
+    };
+    match area {
+        CaptureArea::Viewport | CaptureArea::FullPage => {
+            let metrics = target.execute(&GetLayoutMetrics {}).await?;
+            Ok(match area {
+                CaptureArea::FullPage => clip(
+                    0.0,
+                    0.0,
+                    metrics.css_content_size.width,
+                    metrics.css_content_size.height,
+                ),
+                _ => {
+                    let viewport = metrics.css_visual_viewport;
+                    clip(
+                        viewport.page_x,
+                        viewport.page_y,
+                        viewport.client_width,
+                        viewport.client_height,
+                    )
+                }
+            })
+        }
+        CaptureArea::Element { selector } => {
+            let value = target
+                .execute(&Evaluate {
+                    expression: element_rect_script(selector),
+                    return_by_value: true,
+                })
+                .await?
+                .into_value()
+                .map_err(|e| RuntimeError::InvalidInput {
+                    reason: format!("Failed to locate '{selector}': {e}"),
+                })?;
+            let rect: Option<ElementRect> =
+                serde_json::from_value(value).map_err(RuntimeError::internal)?;
+            match rect {
+                Some(rect) if rect.width > 0.0 && rect.height > 0.0 => {
+                    Ok(clip(rect.x, rect.y, rect.width, rect.height))
+                }
+                Some(_) => Err(RuntimeError::InvalidInput {
+                    reason: format!("Element '{selector}' is not visible"),
+                }),
+                None => Err(RuntimeError::InvalidInput {
+                    reason: format!("No element matches '{selector}'"),
+                }),
+            }
+        }
+        CaptureArea::Clip {
+            x,
+            y,
+            width,
+            height,
+        } => Ok(clip(*x, *y, *width, *height)),
+    }
+}
+
+async fn capture(
+    target: &TargetSession,
+    options: &ScreenshotOptions,
+    scale: f64,
+) -> Result<(Viewport, String), RuntimeError> {
+    let clip = resolve_clip(target, &options.area, scale).await?;
+    let captured = target
+        .execute(&CaptureScreenshot {
+            format: options.format.as_str(),
+            quality: options.quality,
+            clip: Some(clip),
+            /*
+             ** anything outside the viewport is only painted when asked for
+             */
+            capture_beyond_viewport: !matches!(options.area, CaptureArea::Viewport),
+        })
+        .await?;
+    Ok((clip, captured.data))
+}
+
+fn save(target_id: &str, format: ImageFormat, data: &str) -> Result<String, RuntimeError> {
+    let bytes = BASE64
+        .decode(data)
+        .map_err(|e| RuntimeError::internal(format!("Browser sent an invalid image: {e}")))?;
+    let dir = app_data_subdir(SCREENSHOTS_DIR)?;
+    let path = dir.join(format!("{target_id}-{}.{}", now_ms(), format.extension()));
+    std::fs::write(&path, bytes).map_err(|e| RuntimeError::storage(&path, e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/*
+** background tabs do not paint, and captureScreenshot waits for a frame that
+** never comes, so the target is brought to front first. attach, capture, detach.
+*/
+pub async fn capture_screenshot(
+    instance_id: Option<&str>,
+    target_id: &str,
+    options: &ScreenshotOptions,
+) -> Result<Screenshot, RuntimeError> {
+    options.validate()?;
+    let scale = options.scale.unwrap_or(1.0);
+
+    let session = managed_cdp_session(instance_id).await?;
+    session
+        .execute(&ActivateTarget {
+            target_id: target_id.to_string(),
+        })
+        .await?;
+    let target = session.attach_to_target(target_id).await?;
+    let captured = capture(&target, options, scale).await;
+    let _ = target.detach().await;
+    let (clip, data) = captured?;
+    debug!(
+        target_id,
+        width = clip.width,
+        height = clip.height,
+        "captured screenshot"
+    );
+
+    let (data, path) = if options.save {
+        (None, Some(save(target_id, options.format, &data)?))
+    } else {
+        (Some(data), None)
+    };
+    Ok(Screenshot {
+        target_id: target_id.to_string(),
+        format: options.format,
+        mime_type: options.format.mime_type().to_string(),
+        width: clip.width,
+        height: clip.height,
+        scale,
+        captured_at_ms: now_ms(),
+        data,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(format: ImageFormat, quality: Option<u8>, scale: Option<f64>) -> ScreenshotOptions {
+        ScreenshotOptions {
+            format,
+            quality,
+            scale,
+            ..ScreenshotOptions::default()
+        }
+    }
+
+    fn clip(width: f64, height: f64) -> ScreenshotOptions {
+        ScreenshotOptions {
+            area: CaptureArea::Clip {
+                x: 0.0,
+                y: 1200.0,
+                width,
+                height,
+            },
+            ..ScreenshotOptions::default()
+        }
+    }
+
+    fn rejected(options: &ScreenshotOptions) -> bool {
+        matches!(options.validate(), Err(RuntimeError::InvalidInput { .. }))
+    }
+
+    #[test]
+    fn quality_only_for_lossy_formats_and_at_most_100() {
+        assert!(options(ImageFormat::Jpeg, Some(0), None).validate().is_ok());
+        assert!(options(ImageFormat::Webp, Some(100), None)
+            .validate()
+            .is_ok());
+        assert!(rejected(&options(ImageFormat::Jpeg, Some(101), None)));
+        assert!(rejected(&options(ImageFormat::Png, Some(80), None)));
+    }
+
+    #[test]
+    fn scale_is_positive_and_at_most_4() {
+        assert!(options(ImageFormat::Png, None, Some(0.25))
+            .validate()
+            .is_ok());
+        assert!(options(ImageFormat::Png, None, Some(MAX_SCALE))
+            .validate()
+            .is_ok());
+        for scale in [0.0, -1.0, 4.01, f64::NAN] {
+            assert!(
+                rejected(&options(ImageFormat::Png, None, Some(scale))),
+                "{scale}"
+            );
+        }
+    }
+
+    #[test]
+    fn clip_needs_an_area_and_element_a_selector() {
+        assert!(clip(300.0, 200.0).validate().is_ok());
+        assert!(rejected(&clip(0.0, 200.0)));
+        assert!(rejected(&clip(300.0, -1.0)));
+        let element = ScreenshotOptions {
+            area: CaptureArea::Element {
+                selector: "  ".to_string(),
+            },
+            ..ScreenshotOptions::default()
+        };
+        assert!(rejected(&element));
+    }
+}
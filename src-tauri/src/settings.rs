@@ -0,0 +1,39 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri_plugin_store::StoreExt;
+
+use crate::error::RuntimeError;
+use crate::events::app_handle;
+use tracing::warn;
+
+/*
+** backend-owned settings; the webview keeps its own keys in runtime-app.json
+*/
+const SETTINGS_STORE: &str = "runtime-settings.json";
+
+pub fn load_setting<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let store = app_handle().ok()?.store(SETTINGS_STORE).ok()?;
+    let value = store.get(key)?;
+    match serde_json::from_value(value) {
+        Ok(setting) => Some(setting),
+        Err(e) => {
+            warn!("ignoring unreadable setting {key}: {e}");
+            None
+        }
+    }
+}
+
+pub fn save_setting<T: Serialize>(key: &str, value: &T) -> Result<(), RuntimeError> {
+    let failed = |reason: String| RuntimeError::SettingsFailed {
+        key: key.to_string(),
+        reason,
+    };
+    let store = app_handle()?
+        .store(SETTINGS_STORE)
+        .map_err(|e| failed(format!("cannot open {SETTINGS_STORE}: {e}")))?;
+    let value = serde_json::to_value(value).map_err(|e| failed(e.to_string()))?;
+    store.set(key, value);
+    store
+        .save()
+        .map_err(|e| failed(format!("cannot write {SETTINGS_STORE}: {e}")))
+}
@@ -1,49 +1,153 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::process::Child;
 
+use crate::browser_logs::BrowserLogs;
+use crate::browser_manager::LaunchSpec;
+use crate::browser_version::Compatibility;
+use crate::cdp::CdpSession;
+use crate::endpoint::CdpEndpoint;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BrowserConfig {
-    pub id: String, // chrome, edge for now.
+    pub id: String, // chrome, edge, chromium, brave, vivaldi, opera, arc
     pub name: String,
     pub path: String,
+    #[serde(default)]
+    pub channel: Option<String>, // stable, beta, dev, canary, ...
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub extra_args: Vec<String>, // appended to the launch command line
+    #[serde(default)]
+    pub custom: bool, // added by the user rather than detected
+}
+
+impl BrowserConfig {
+    /*
+     ** custom entries carry a user-chosen id; what kind of browser they are
+     ** still follows from the executable
+     */
+    pub fn family(&self) -> &str {
+        if self.custom {
+            crate::utils::browser_id_for_path(&self.path)
+        } else {
+            &self.id
+        }
+    }
+}
+
+/*
+** how we talk to a browser we launch: over the private fds 3/4 pipe, or over
+** a TCP DevTools port (needed by the webview's puppeteer and for external browsers)
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugTransport {
+    Pipe,
+    Tcp,
+}
+
+impl Default for DebugTransport {
+    fn default() -> Self {
+        if cfg!(unix) {
+            DebugTransport::Pipe
+        } else {
+            DebugTransport::Tcp
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct ManageableBrowserInstance {
     pub child: Option<Child>,
+    pub browser_id: String,
     pub path: String,
-    pub port: u16,
-    #[allow(dead_code)]
-    pub ws_url: String,
+    pub transport: DebugTransport,
+    pub endpoint: Option<CdpEndpoint>,
+    pub ws_url: Option<String>,
     pub launched_by_app: bool,
+    pub cdp: Option<CdpSession>,
+    pub logs: Option<BrowserLogs>,
+    pub launch: Option<LaunchSpec>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct UserQuery {
-    pub query: String,
-    pub session_id: Option<String>,
-    pub current_url: Option<String>,
-    pub page_context: Option<serde_json::Value>,
+pub struct BrowserInstanceInfo {
+    pub instance_id: String,
+    pub browser_id: String,
+    pub path: String,
+    pub transport: DebugTransport,
+    pub endpoint: Option<CdpEndpoint>,
+    pub ws_url: Option<String>,
+    pub profile: Option<String>,
+    pub launched_by_app: bool,
+    pub cdp_connected: bool,
+    pub selected: bool,
+    pub restarting: bool,
+}
+
+/*
+** what profile.json keeps next to a profile's user-data dir
+*/
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BrowserProfile {
+    pub name: String,
+    pub browser_id: String,
+    pub created_at_ms: u64,
+    pub last_used_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BrowserProfileInfo {
+    #[serde(flatten)]
+    pub profile: BrowserProfile,
+    pub size_bytes: u64,
+    pub in_use: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSource {
+    PortScan,
+    ActivePortFile,
+}
+
+/*
+** a live DevTools endpoint found on this machine, launched by us or not
+*/
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiscoveredEndpoint {
+    pub port: u16,
+    pub ws_url: String,
+    pub browser_type: String,
+    pub product: String,
+    pub version: Option<String>,
+    pub user_agent: String,
+    pub target_count: usize,
+    pub compatibility: Compatibility,
+    pub source: EndpointSource,
+    pub user_data_dir: Option<String>,
 }
 
+/*
+** one tab, worker or other target of a browser instance
+*/
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct LLMActionResponse {
-    pub task_id: String,
-    pub action: String,
-    pub parameters: Option<HashMap<String, serde_json::Value>>,
-    pub thought: Option<String>,
-    pub speak_to_user: Option<String>,
-    pub session_id: Option<String>,
+pub struct BrowserTarget {
+    pub target_id: String,
+    #[serde(rename = "type")]
+    pub target_type: String,
+    pub title: String,
+    pub url: String,
+    pub attached: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PuppeteerExecutionResult {
-    pub task_id: String,
-    pub action: String,
-    pub status: String,
-    pub data: Option<serde_json::Value>,
-    pub error_message: Option<String>,
-    pub current_url: Option<String>,
-    pub page_context: Option<serde_json::Value>,
+pub struct LaunchedInstance {
+    pub instance_id: String,
+    pub ws_url: Option<String>,
+    /*
+     ** set when the browser is up but its starter page could not be opened
+     */
+    pub starter_page_error: Option<String>,
 }
@@ -28,4 +28,9 @@ pub struct SkillStep {
     pub index: Option<u32>,
     #[serde(default)]
     pub output_key: Option<String>,
+    /*
+     ** wait_for_download
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
 }
@@ -1,30 +1,40 @@
+use crate::error::RuntimeError;
+use crate::http::{self, HttpProfile};
 use crate::sketchs_browser::WebsiteSkills;
-use reqwest::Client;
+use tracing::debug;
 
 pub async fn download_skill_json(
     domain: String,
     company: Option<String>,
     repo: Option<String>,
     branch: String,
-) -> Result<WebsiteSkills, String> {
+) -> Result<WebsiteSkills, RuntimeError> {
     let company: String = company.unwrap_or_else(|| "runtime-org".to_string());
     let repo: String = repo.unwrap_or_else(|| "sk".to_string());
     let url: String =
         format!("https://raw.githubusercontent.com/{company}/{repo}/{branch}/skills/{domain}.json");
-    println!("downloading skills from: {}", url);
-    let text: String = Client::new()
-        .get(url)
-        .send()
+    debug!("downloading skills from: {}", url);
+    let text: String = http::get(HttpProfile::Remote, &url)
         .await
-        .map_err(|e| format!("Failed to download skill file for {domain}: {e}"))?
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| RuntimeError::SkillDownload {
+            domain: domain.clone(),
+            reason: e.to_string(),
+        })?
         .text()
         .await
-        .map_err(|e| format!("Failed to download skill file for {company}.{domain}: {e}"))?;
+        .map_err(|e| RuntimeError::SkillDownload {
+            domain: domain.clone(),
+            reason: e.to_string(),
+        })?;
 
-    let parsed: WebsiteSkills = serde_json::from_str(&text)
-        .map_err(|e| format!("Failed to parse skill file for {domain}: {e}"))?;
+    let parsed: WebsiteSkills =
+        serde_json::from_str(&text).map_err(|e| RuntimeError::SkillParse {
+            domain: domain.clone(),
+            reason: e.to_string(),
+        })?;
 
-    println!("parsed: {:?}", parsed);
+    debug!("parsed: {:?}", parsed);
 
     Ok(parsed)
 }
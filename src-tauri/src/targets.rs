@@ -0,0 +1,107 @@
+use crate::browser_manager::managed_cdp_session;
+use crate::cdp::{ActivateTarget, CdpSession, CloseTarget, CreateTarget, GetTargets, Reload};
+use crate::endpoint::CdpEndpoint;
+use crate::error::RuntimeError;
+use crate::network;
+use crate::registry::BROWSER_MANAGER;
+use crate::sketchs::BrowserTarget;
+
+/*
+** instances with a DevTools port are managed over its /json endpoints;
+** pipe browsers only have their CDP session
+*/
+enum Route {
+    Http(CdpEndpoint),
+    Cdp(CdpSession),
+}
+
+async fn route(instance_id: Option<&str>) -> Result<Route, RuntimeError> {
+    match BROWSER_MANAGER.snapshot(instance_id).await?.endpoint {
+        Some(endpoint) => Ok(Route::Http(endpoint)),
+        None => managed_cdp_session(instance_id).await.map(Route::Cdp),
+    }
+}
+
+pub async fn list_targets(instance_id: Option<&str>) -> Result<Vec<BrowserTarget>, RuntimeError> {
+    match route(instance_id).await? {
+        Route::Http(endpoint) => network::list_targets(&endpoint).await,
+        Route::Cdp(session) => Ok(session
+            .execute(&GetTargets {})
+            .await?
+            .target_infos
+            .into_iter()
+            .map(|info| BrowserTarget {
+                target_id: info.target_id,
+                target_type: info.target_type,
+                title: info.title,
+                url: info.url,
+                attached: info.attached,
+            })
+            .collect()),
+    }
+}
+
+pub async fn activate_target(
+    instance_id: Option<&str>,
+    target_id: &str,
+) -> Result<(), RuntimeError> {
+    match route(instance_id).await? {
+        Route::Http(endpoint) => network::activate_target(&endpoint, target_id).await,
+        Route::Cdp(session) => session
+            .execute(&ActivateTarget {
+                target_id: target_id.to_string(),
+            })
+            .await
+            .map(|_| ()),
+    }
+}
+
+pub async fn close_target(instance_id: Option<&str>, target_id: &str) -> Result<(), RuntimeError> {
+    match route(instance_id).await? {
+        Route::Http(endpoint) => network::close_target(&endpoint, target_id).await,
+        Route::Cdp(session) => {
+            let closed = session
+                .execute(&CloseTarget {
+                    target_id: target_id.to_string(),
+                })
+                .await?;
+            if closed.success {
+                Ok(())
+            } else {
+                Err(RuntimeError::cdp(format!(
+                    "Browser refused to close target {target_id}"
+                )))
+            }
+        }
+    }
+}
+
+/*
+** there is no HTTP route for this one: attach, reload, detach
+*/
+pub async fn reload_target(
+    instance_id: Option<&str>,
+    target_id: &str,
+    ignore_cache: bool,
+) -> Result<(), RuntimeError> {
+    let session = managed_cdp_session(instance_id).await?;
+    let target = session.attach_to_target(target_id).await?;
+    let reloaded = target.execute(&Reload { ignore_cache }).await;
+    let _ = target.detach().await;
+    reloaded.map(|_| ())
+}
+
+/*
+** open `url` in a new tab; returns its target id
+*/
+pub async fn open_page(instance_id: Option<&str>, url: &str) -> Result<String, RuntimeError> {
+    match route(instance_id).await? {
+        Route::Http(endpoint) => network::create_new_page(&endpoint, Some(url)).await,
+        Route::Cdp(session) => session
+            .execute(&CreateTarget {
+                url: url.to_string(),
+            })
+            .await
+            .map(|created| created.target_id),
+    }
+}
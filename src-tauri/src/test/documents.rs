@@ -0,0 +1,93 @@
+use super::fixture;
+use crate::documents::{extract_document, DocumentKind};
+use crate::error::RuntimeError;
+
+#[test]
+fn splits_docx_pages_at_page_breaks() {
+    let document = extract_document(&fixture("sample.docx"), None).unwrap();
+
+    assert_eq!(document.kind, DocumentKind::Docx);
+    assert_eq!(document.pages.len(), 2);
+    assert_eq!(
+        document.pages[0].text,
+        "Meeting notes\nAttendees: Ana & Ben\nBudget\tapproved\nItem\tOwner\nDraft report\tAna"
+    );
+    assert_eq!(
+        document.pages[1].text,
+        "Next steps\nSend the report\nby Friday."
+    );
+    assert_eq!(
+        document.text,
+        format!("{}\n\n{}", document.pages[0].text, document.pages[1].text)
+    );
+}
+
+#[test]
+fn reads_docx_core_properties() {
+    let metadata = extract_document(&fixture("sample.docx"), None)
+        .unwrap()
+        .metadata;
+
+    assert_eq!(metadata.title.as_deref(), Some("Meeting notes"));
+    assert_eq!(metadata.author.as_deref(), Some("Ana Lima"));
+    assert_eq!(metadata.subject.as_deref(), Some("Weekly sync"));
+    assert_eq!(metadata.created.as_deref(), Some("2024-03-15T10:30:00Z"));
+    assert_eq!(metadata.page_count, 2);
+    assert_eq!(metadata.word_count, 20);
+}
+
+#[test]
+fn keeps_only_rendered_html_text() {
+    let document = extract_document(&fixture("sample.html"), None).unwrap();
+
+    assert_eq!(document.kind, DocumentKind::Html);
+    assert_eq!(document.pages.len(), 1);
+    assert_eq!(
+        document.text,
+        "Thanks for your order\n\n\
+         Order #4821 was placed on 15 March 2024.\n\n\
+         - Desk lamp × 1\n\
+         - Light bulb × 4\n\n\
+         Subtotal\t42.00\n\
+         Total\t45.90\n\n\
+         Questions?\n\
+         Reply to this email."
+    );
+    assert_eq!(
+        document.metadata.title.as_deref(),
+        Some("Order confirmation")
+    );
+    assert_eq!(document.metadata.author.as_deref(), Some("Example Shop"));
+}
+
+#[test]
+fn cleans_plain_text_and_splits_on_form_feeds() {
+    let document = extract_document(&fixture("sample.txt"), None).unwrap();
+
+    assert_eq!(document.kind, DocumentKind::Text);
+    assert_eq!(document.pages.len(), 2);
+    assert_eq!(
+        document.pages[0].text,
+        "Release notes\n\nVersion 1.2\n- Faster startup"
+    );
+    assert_eq!(document.pages[1].text, "Known issues\n- None so far");
+    assert_eq!(document.metadata.title, None);
+}
+
+#[test]
+fn rejects_binary_files() {
+    let binary = std::env::temp_dir().join(format!("runtime-binary-{}.dat", std::process::id()));
+    std::fs::write(&binary, [0x89, b'P', b'N', b'G', 0, 0, 0, 13]).unwrap();
+    let document = extract_document(&binary, None);
+    let _ = std::fs::remove_file(&binary);
+
+    assert!(matches!(document, Err(RuntimeError::Unsupported { .. })));
+}
+
+#[test]
+fn rejects_relative_paths() {
+    assert!(matches!(
+        extract_document(std::path::Path::new("src/test/fixtures/sample.txt"), None),
+        Err(RuntimeError::InvalidInput { .. })
+    ));
+}
@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+mod documents;
+mod pdf;
+mod readability;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/test/fixtures")
+        .join(name)
+}
@@ -0,0 +1,77 @@
+use super::fixture;
+use crate::documents::{extract_document, DocumentKind};
+use crate::error::RuntimeError;
+
+#[test]
+fn extracts_text_page_by_page() {
+    let document = extract_document(&fixture("sample.pdf"), None).unwrap();
+
+    assert_eq!(document.kind, DocumentKind::Pdf);
+    assert_eq!(document.mime_type, "application/pdf");
+    assert_eq!(document.pages.len(), 2);
+    assert_eq!(document.pages[0].number, 1);
+    assert!(document.pages[0].text.contains("Invoice 2024-0117"));
+    assert!(document.pages[0].text.contains("Total due: 1,250.00 EUR"));
+    assert!(!document.pages[0].text.contains("Payment terms"));
+    assert!(document.pages[1]
+        .text
+        .contains("Pay within 30 days of the invoice date."));
+    assert!(!document.truncated);
+}
+
+#[test]
+fn reads_the_info_dictionary() {
+    let metadata = extract_document(&fixture("sample.pdf"), None)
+        .unwrap()
+        .metadata;
+
+    assert_eq!(metadata.title.as_deref(), Some("Invoice 2024-0117"));
+    /*
+     ** stored as UTF-16BE in the fixture
+     */
+    assert_eq!(metadata.author.as_deref(), Some("José Müller"));
+    assert_eq!(metadata.subject.as_deref(), Some("Sample invoice"));
+    assert_eq!(metadata.created.as_deref(), Some("2024-03-15T10:30:00Z"));
+    assert_eq!(metadata.page_count, 2);
+    assert!(metadata.word_count >= 15);
+}
+
+#[test]
+fn caps_text_at_max_chars() {
+    let full = extract_document(&fixture("sample.pdf"), None).unwrap();
+    let first_page = full.pages[0].text.chars().count();
+
+    let capped = extract_document(&fixture("sample.pdf"), Some(first_page + 5)).unwrap();
+    assert!(capped.truncated);
+    assert_eq!(capped.pages.len(), 2);
+    assert_eq!(capped.pages[1].text.chars().count(), 5);
+    assert_eq!(capped.metadata.page_count, 2);
+
+    let first_only = extract_document(&fixture("sample.pdf"), Some(first_page)).unwrap();
+    assert!(first_only.truncated);
+    assert_eq!(first_only.pages.len(), 1);
+    assert_eq!(first_only.text, full.pages[0].text);
+}
+
+#[test]
+fn detects_a_pdf_without_its_extension() {
+    let copy = std::env::temp_dir().join(format!("runtime-pdf-{}.bin", std::process::id()));
+    std::fs::copy(fixture("sample.pdf"), &copy).unwrap();
+    let document = extract_document(&copy, None);
+    let _ = std::fs::remove_file(&copy);
+
+    assert_eq!(document.unwrap().kind, DocumentKind::Pdf);
+}
+
+#[test]
+fn reports_a_broken_pdf() {
+    let broken = std::env::temp_dir().join(format!("runtime-broken-{}.pdf", std::process::id()));
+    std::fs::write(&broken, b"%PDF-1.4\nthis is not really a pdf").unwrap();
+    let document = extract_document(&broken, None);
+    let _ = std::fs::remove_file(&broken);
+
+    assert!(matches!(
+        document,
+        Err(RuntimeError::ExtractionFailed { .. })
+    ));
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use crate::readability::{estimate_tokens, html_to_markdown};
+
+const FLIGHTS_URL: &str = "https://www.google.com/travel/flights/search?tfs=CBwQAhoe";
+
+/*
+** a saved Google Flights results page: thousands of nested divs, a
+** calendar and filter dialogs, tooltips and a footer around the results
+*/
+fn google_flights() -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test/sample.xml");
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn keeps_the_results_of_a_google_page() {
+    let page = html_to_markdown(&google_flights(), Some(FLIGHTS_URL), 100_000);
+
+    assert_eq!(page.title.as_deref(), Some("Google Flights"));
+    assert!(page
+        .description
+        .unwrap()
+        .starts_with("Use Google Flights to explore cheap flights"));
+    assert_eq!(page.url.as_deref(), Some(FLIGHTS_URL));
+    assert!(!page.truncated);
+
+    let markdown = page.markdown;
+    assert!(markdown.starts_with("## Search results"));
+    assert!(markdown.contains("\n### Top departing flights\n"));
+    assert!(markdown.contains("\n### Other departing flights\n"));
+    assert_eq!(markdown.matches("\n- ").count(), 12);
+    assert!(markdown.contains("- 9:45 PM – 11:55 PM\n  Ryanair Operated by Malta Air\n"));
+    assert!(markdown.contains("  3 stops in ZEU, KWQ, FRA 3 stops 7 hr 51 min\n"));
+}
+
+#[test]
+fn strips_google_boilerplate() {
+    let markdown = html_to_markdown(&google_flights(), Some(FLIGHTS_URL), 100_000).markdown;
+
+    for boilerplate in [
+        /*
+         ** date picker and passenger dialog
+         */
+        "February 2026",
+        "Add infant in seat",
+        /*
+         ** tooltip dialogs and filter buttons
+         */
+        "Looking for stress-free travel?",
+        "All filters",
+        /*
+         ** footer, script and the hidden <view-header>
+         */
+        "Prices are final prices",
+        "performance.mark",
+        "noindex",
+    ] {
+        assert!(!markdown.contains(boilerplate), "kept {boilerplate:?}");
+    }
+    assert!(!markdown.contains('<'));
+    assert!(estimate_tokens(&markdown) < 1_500);
+}
+
+#[test]
+fn caps_output_by_token_budget() {
+    let html = google_flights();
+    let full = html_to_markdown(&html, Some(FLIGHTS_URL), 100_000);
+    let capped = html_to_markdown(&html, Some(FLIGHTS_URL), 200);
+
+    assert!(capped.truncated);
+    assert!(capped.token_estimate <= 200);
+    assert_eq!(capped.token_estimate, estimate_tokens(&capped.markdown));
+    assert!(full.markdown.starts_with(&capped.markdown));
+    /*
+     ** whole blocks only: the cut lands between two of them
+     */
+    assert!(full.markdown[capped.markdown.len()..].starts_with("\n\n"));
+
+    let tiny = html_to_markdown("<p>one two three four five six</p>", None, 2);
+    assert!(tiny.truncated);
+    assert_eq!(tiny.markdown, "one two");
+}
+
+#[test]
+fn keeps_links_and_tables_of_an_article() {
+    let html = r##"<html><head><title>Release 2.0</title></head><body>
+        <nav><a href="/">Home</a> <a href="/blog">Blog</a></nav>
+        <div class="cookie-banner">We use cookies. <button>Accept</button></div>
+        <article>
+          <header><h1>Release 2.0</h1><p>By <a href="/authors/ana">Ana</a></p></header>
+          <p>Read the <a href="docs/upgrade.html">upgrade guide</a> first,
+             or <a href="javascript:void(0)">nothing</a> and <a href="#notes">skip</a>.</p>
+          <table>
+            <thead><tr><th>Feature</th><th>Status</th></tr></thead>
+            <tbody>
+              <tr><td>Sync</td><td><b>done</b></td></tr>
+              <tr><td>Export | import</td><td>beta</td></tr>
+            </tbody>
+          </table>
+          <ol><li>Back up</li><li>Run <code>migrate --all</code></li></ol>
+          <pre>line one
+  indented</pre>
+          <aside class="share">Share on social</aside>
+        </article>
+        <footer>© Example</footer>
+        </body></html>"##;
+    let page = html_to_markdown(html, Some("https://example.com/blog/release-2"), 1000);
+
+    assert_eq!(page.title.as_deref(), Some("Release 2.0"));
+    assert_eq!(
+        page.markdown,
+        "# Release 2.0\n\n\
+         By [Ana](https://example.com/authors/ana)\n\n\
+         Read the [upgrade guide](https://example.com/blog/docs/upgrade.html) first, or nothing and skip.\n\n\
+         | Feature | Status |\n\
+         | --- | --- |\n\
+         | Sync | **done** |\n\
+         | Export \\| import | beta |\n\n\
+         1. Back up\n\
+         2. Run `migrate --all`\n\n\
+         ```\nline one\n  indented\n```"
+    );
+}
+
+#[test]
+fn falls_back_to_the_densest_container() {
+    let story: String = (1..=4)
+        .map(|n| {
+            format!(
+                "<p>Paragraph {n} is long enough to count as the main content of this page.</p>"
+            )
+        })
+        .collect();
+    let html = format!(
+        "<body><div id=menu><p>Home</p><p>About</p></div>\
+         <div id=story>{story}</div>\
+         <div id=links><p>Terms</p></div></body>"
+    );
+    let markdown = html_to_markdown(&html, None, 1000).markdown;
+
+    assert_eq!(markdown.matches("is long enough").count(), 4);
+    assert!(!markdown.contains("About"));
+    assert!(!markdown.contains("Terms"));
+}
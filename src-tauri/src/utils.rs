@@ -1,18 +1,128 @@
+#[cfg(not(target_os = "linux"))]
 use crate::sketchs::BrowserConfig;
+use std::path::Path;
+#[cfg(not(target_os = "linux"))]
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(not(target_os = "linux"))]
+use tracing::debug;
 
+#[cfg(not(target_os = "linux"))]
 pub fn check_browser(id: &str, name: &str, paths: &[&str]) -> Option<BrowserConfig> {
     for path_str in paths {
         let path = PathBuf::from(path_str);
 
         if path.exists() && path.is_file() {
-            println!("Found browser: {} at {}", name, path_str);
+            debug!("Found browser: {} at {}", name, path_str);
             return Some(BrowserConfig {
                 id: id.to_string(),
                 name: name.to_string(),
                 path: path.to_string_lossy().to_string(),
+                channel: None,
+                version: None,
+                extra_args: Vec::new(),
+                custom: false,
             });
         }
     }
     None
 }
+
+pub fn browser_id_for_path(path: &str) -> &'static str {
+    let lower = path.to_lowercase();
+    if lower.contains("edge") || lower.contains("msedge") {
+        "edge"
+    } else if lower.contains("brave") {
+        "brave"
+    } else if lower.contains("vivaldi") {
+        "vivaldi"
+    } else if lower.contains("opera") {
+        "opera"
+    } else if lower.contains("chromium") {
+        "chromium"
+    } else if is_arc_path(path) {
+        "arc"
+    } else {
+        "chrome"
+    }
+}
+
+/*
+** Arc by its executable ("Arc", "Arc.exe", "arc" on Linux) or its macOS
+** bundle; a bare substring match would also catch e.g. "/home/marc/" or "search"
+*/
+pub fn is_arc_path(path: &str) -> bool {
+    let path = Path::new(path);
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("arc"))
+        || path.components().any(|part| part.as_os_str() == "Arc.app")
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/*
+** by extension only; good enough for listing files, not for trusting them
+*/
+pub fn mime_type_for_path(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "htm" | "html" => "text/html",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_is_matched_by_executable_or_bundle() {
+        assert!(is_arc_path("/Applications/Arc.app/Contents/MacOS/Arc"));
+        assert!(is_arc_path("/opt/arc/Arc"));
+        assert!(is_arc_path("/usr/bin/arc"));
+        assert!(is_arc_path("/opt/arc/arc"));
+        assert!(!is_arc_path("/opt/arc/chrome"));
+        assert!(!is_arc_path("/home/marc/bin/chrome"));
+        assert!(!is_arc_path("/opt/research/chromium"));
+        assert_eq!(
+            browser_id_for_path("/home/marc/bin/google-chrome"),
+            "chrome"
+        );
+        assert_eq!(
+            browser_id_for_path("/Applications/Arc.app/Contents/MacOS/Arc"),
+            "arc"
+        );
+        assert_eq!(browser_id_for_path("/usr/bin/arc"), "arc");
+    }
+}
@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::browser_manager::{shutdown_browser, spawn_instance};
+use crate::cdp::CdpSession;
+use crate::downloads::manage_launched;
+use crate::endpoint::CdpEndpoint;
+use crate::events::{emit_lifecycle, BrowserLifecycleEvent, BROWSER_CRASHED, BROWSER_RECONNECTED};
+use crate::http::{self, HttpProfile};
+use crate::registry::{CrashedInstance, BROWSER_MANAGER};
+use tracing::{error, info, info_span, warn, Instrument};
+
+pub static RESTART_POLICY: Lazy<RwLock<RestartPolicy>> =
+    Lazy::new(|| RwLock::new(RestartPolicy::default()));
+
+/*
+** how the watchdog treats an app-launched browser that died or stopped answering.
+** browsers we merely attached to are never relaunched.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    pub max_restarts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub probe_interval_ms: u64,
+    pub probe_timeout_ms: u64,
+    pub unresponsive_after: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_restarts: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            probe_interval_ms: 2_000,
+            probe_timeout_ms: 3_000,
+            unresponsive_after: 3,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /*
+     ** 1st attempt waits initial_backoff_ms, every further one doubles it
+     */
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+enum Health {
+    Gone,
+    Exited(String),
+    Probe {
+        cdp: Option<CdpSession>,
+        endpoint: Option<CdpEndpoint>,
+    },
+}
+
+/*
+** the manager checks the process; the network probe happens here
+*/
+async fn inspect(instance_id: &str) -> Health {
+    let Ok(instance) = BROWSER_MANAGER.snapshot(Some(instance_id)).await else {
+        return Health::Gone;
+    };
+    if let Some(reason) = instance.exited {
+        return Health::Exited(reason);
+    }
+    Health::Probe {
+        cdp: instance.cdp,
+        endpoint: instance.endpoint,
+    }
+}
+
+async fn probe(cdp: Option<CdpSession>, endpoint: Option<CdpEndpoint>, timeout: Duration) -> bool {
+    if let Some(cdp) = cdp {
+        return matches!(
+            tokio::time::timeout(
+                timeout,
+                cdp.send("Browser.getVersion", serde_json::json!({}))
+            )
+            .await,
+            Ok(Ok(_))
+        );
+    }
+
+    /*
+     ** no live session: a pipe browser without one is as good as dead,
+     ** a port browser can still be asked over HTTP
+     */
+    let Some(endpoint) = endpoint else {
+        return false;
+    };
+    http::client(HttpProfile::Probe)
+        .get(endpoint.http_url("/json/version"))
+        .timeout(timeout)
+        .send()
+        .await
+        .map(|r| r.status().is_success())
+        .unwrap_or(false)
+}
+
+/*
+** watch one registry entry until it is closed through the app, or until it
+** dies and the restart policy says to give up
+*/
+pub fn supervise(instance_id: String) {
+    let span = info_span!("instance", %instance_id);
+    tokio::spawn(watch(instance_id).instrument(span));
+}
+
+async fn watch(instance_id: String) {
+    let mut failed_probes = 0;
+    let mut restarts = 0;
+
+    loop {
+        let policy = RESTART_POLICY.read().await.clone();
+        tokio::time::sleep(Duration::from_millis(policy.probe_interval_ms)).await;
+
+        let reason = match inspect(&instance_id).await {
+            Health::Gone => return,
+            Health::Exited(reason) => reason,
+            Health::Probe { cdp, endpoint } => {
+                if probe(
+                    cdp,
+                    endpoint,
+                    Duration::from_millis(policy.probe_timeout_ms),
+                )
+                .await
+                {
+                    failed_probes = 0;
+                    continue;
+                }
+                failed_probes += 1;
+                if failed_probes < policy.unresponsive_after {
+                    continue;
+                }
+                format!("DevTools endpoint did not answer {failed_probes} probes in a row")
+            }
+        };
+        failed_probes = 0;
+
+        let Some(CrashedInstance {
+            child,
+            browser_id,
+            ws_url,
+            launch,
+        }) = BROWSER_MANAGER.crash(&instance_id).await
+        else {
+            return;
+        };
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        error!("browser {instance_id} crashed: {reason}");
+        emit_lifecycle(
+            BROWSER_CRASHED,
+            BrowserLifecycleEvent {
+                instance_id: instance_id.clone(),
+                browser_id: browser_id.clone(),
+                ws_url,
+                reason: Some(reason),
+                restart_attempt: None,
+            },
+        );
+
+        let Some(spec) = launch else {
+            BROWSER_MANAGER.remove(&instance_id).await;
+            return;
+        };
+
+        loop {
+            let policy = RESTART_POLICY.read().await.clone();
+            if !policy.enabled || restarts >= policy.max_restarts {
+                info!("not relaunching browser {instance_id} ({restarts} restarts so far)");
+                BROWSER_MANAGER.remove(&instance_id).await;
+                return;
+            }
+            restarts += 1;
+            tokio::time::sleep(policy.backoff(restarts)).await;
+
+            /*
+             ** closing the instance while it was down removes its entry
+             */
+            if !BROWSER_MANAGER
+                .snapshot(Some(&instance_id))
+                .await
+                .is_ok_and(|instance| instance.restarting)
+            {
+                info!("browser {instance_id} was closed while waiting for a restart");
+                return;
+            }
+
+            info!("relaunching browser {instance_id} (attempt {restarts})");
+            match spawn_instance(spec.clone()).await {
+                Ok(instance) => {
+                    let ws_url = instance.ws_url.clone();
+                    if let Err(mut orphan) = BROWSER_MANAGER.restore(&instance_id, instance).await {
+                        info!("browser {instance_id} was closed while relaunching");
+                        shutdown_browser(&mut orphan, true).await;
+                        return;
+                    }
+                    manage_launched(instance_id.clone());
+                    emit_lifecycle(
+                        BROWSER_RECONNECTED,
+                        BrowserLifecycleEvent {
+                            instance_id: instance_id.clone(),
+                            browser_id: browser_id.clone(),
+                            ws_url,
+                            reason: None,
+                            restart_attempt: Some(restarts),
+                        },
+                    );
+                    break;
+                }
+                Err(e) => warn!("relaunch of browser {instance_id} failed: {e}"),
+            }
+        }
+    }
+}
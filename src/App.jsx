@@ -138,8 +138,13 @@ function App() {
       // if no valid WS, launch new browser
       if (!ws) {
         setIsConnected(false);
-        ws = await invoke("launch_browser", { browserPath: path });
-        console.log("New browser launched with WS:", ws);
+        // puppeteer needs a websocket endpoint, so opt into the tcp transport
+        const launched = await invoke("launch_browser", { browserPath: path, transport: "tcp" });
+        ws = launched.ws_url;
+        console.log("New browser launched with WS:", ws, "as", launched.instance_id);
+        if (launched.starter_page_error) {
+          console.warn("Starter page could not be opened:", launched.starter_page_error);
+        }
       }
 
       /*
@@ -1,10 +1,34 @@
 import { v4 as uuidv4 } from "uuid";
-import { ExecOps } from "./task.browser.schemas";
-import { actionDesc, StepRunnerRegistry } from "./task.browser.runner";
+import { invoke } from "@tauri-apps/api/core";
+import { ExecOps, WebsiteSkills } from "./task.browser.schemas";
+import { actionDesc, DownloadContext, StepRunnerRegistry } from "./task.browser.runner";
 import { emit } from "./task.browser.helpers";
 
 const MAX_ATTEMPTS = 3;
 
+/*
+** the browser only reports downloads once they are enabled, so do it before
+** the step that starts one runs. returns the instance the skill drives.
+*/
+async function enableDownloads(plan: any, skillMaps: WebsiteSkills[]): Promise<string | undefined> {
+    const skills = skillMaps.flatMap(s => s.skills);
+    const waits = plan.skills.some((skillCall: { skill: string }) =>
+        skills.find(s => s.name === skillCall.skill)?.steps?.some(step => step.action === "wait_for_download")
+    );
+    if (!waits) return undefined;
+
+    try {
+        const instances = await invoke<Array<{ instance_id: string; selected: boolean }>>("list_browser_instances");
+        const instanceId = instances.find(instance => instance.selected)?.instance_id;
+        if (!instanceId) return undefined;
+        await invoke("enable_downloads", { instanceId });
+        return instanceId;
+    } catch (err) {
+        console.warn("Failed to enable downloads:", err);
+        return undefined;
+    }
+}
+
 function findValidUrl(list: any[]) {
     // go through the list and find the first valid raw element
     for (const item of list) if (item.link?.startsWith("https://")) return item;
@@ -19,6 +43,8 @@ export async function executeMacroPlan({
     skillMaps
 }: ExecOps) {
     const page = await pageManager();
+    const downloadInstanceId = await enableDownloads(plan, skillMaps);
+    let stepStartedMs = Date.now();
     const history: Record<string, unknown> = {};
     const historyLog: Array<{
         stepName: string;
@@ -80,6 +106,11 @@ export async function executeMacroPlan({
         }
 
         for (const step of skillDef.steps || []) {
+            /*
+            ** a wait_for_download step looks for downloads started since the step before it
+            */
+            if (step.action !== "wait_for_download") stepStartedMs = Date.now();
+            const download: DownloadContext = { instanceId: downloadInstanceId, sinceMs: stepStartedMs };
             let attempt = 0;
             let success = false;
             let lastError: unknown;
@@ -127,7 +158,8 @@ export async function executeMacroPlan({
                     const result = await StepRunnerRegistry[step.action](step, {
                         page,
                         params: stepParams,
-                        browser
+                        browser,
+                        download
                     });
                     console.log("------->", step.action, result);
 
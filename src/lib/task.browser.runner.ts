@@ -1,14 +1,25 @@
+import { invoke } from "@tauri-apps/api/core";
 import { SkillStep } from "./task.browser.schemas";
 import { runAtomicStep } from "./task.browser.runner.atomic";
 import { Browser, Page } from "puppeteer-core/lib/esm/puppeteer/puppeteer-core-browser.js";
 
+/*
+** which browser instance the skill drives, and when the step before the
+** current one started; a download that began earlier is not ours
+*/
+export interface DownloadContext {
+  instanceId?: string;
+  sinceMs: number;
+}
+
 interface Runner {
   (
     step: SkillStep, 
     ctx: { 
       browser: Browser, 
       page: Page, 
-      params: Record<string, unknown> 
+      params: Record<string, unknown>,
+      download?: DownloadContext
     }): Promise<unknown>;
 }
 
@@ -64,6 +75,21 @@ export const StepRunnerRegistry: Record<SkillStep["action"], Runner> = {
   ** handle scroll down
   */
   scroll_down: async (step, { page, params }) => runAtomicStep({ step, page, params }),
+
+  /*
+  ** handle wait for download: the file the previous step started, once the browser is done with it
+  */
+  wait_for_download: async (step, { download: ctx }) => {
+    const download = await invoke<{ state: string }>("wait_for_download", {
+      instanceId: ctx?.instanceId,
+      timeoutMs: step.timeout_ms,
+      sinceMs: ctx?.sinceMs
+    });
+    if (download.state !== "completed") {
+      return { success: false, error: `download ${download.state}` };
+    }
+    return { success: true, data: download };
+  },
 };
 
 /**
@@ -82,7 +108,8 @@ export function actionDesc(action: SkillStep["action"]): string {
     navigate_back: "Navigates back to the previous page in the browser history",
     click_element_by_index: "Clicks on a specific element from a list of elements, identified by index position",
     navigate_to_url: "Navigates to a specific URL",
-    scroll_down: "Scrolls down the page"
+    scroll_down: "Scrolls down the page",
+    wait_for_download: "Waits for a file download started by the previous step to finish"
   };
 
   return descriptions[action] || `Unknown action: ${action}`;
@@ -103,7 +130,8 @@ export function getAllActionDescriptions(): Record<SkillStep["action"], string>
     "navigate_back",
     "click_element_by_index",
     "navigate_to_url",
-    "scroll_down"
+    "scroll_down",
+    "wait_for_download"
   ];
 
   return actions.reduce((acc, action) => {
@@ -27,7 +27,8 @@ export type StepAction =
    | "navigate_back"
    | "click_element_by_index"
    | "navigate_to_url"
-   | "scroll_down";
+   | "scroll_down"
+   | "wait_for_download";
 
 export interface ExecOps {
     taskId: string;
@@ -49,6 +50,7 @@ export interface SkillStep {
     schema?: Record<string, string>;
     times?: number;
     split?: "before" | "after";
+    timeout_ms?: number;
 }
 
 